@@ -1,12 +1,20 @@
 mod requests;
 
-use adobededrmtools::{Acsm, AdobeAccount};
+use std::{sync::Arc, time::Duration};
+
+use adobededrmtools::{
+    Acsm, ActivationLimitPolicy, AdobeAccount, CreateAccountParams, DirectoryAccountArchive,
+    FileServicesInfoCache, FulfillParams, Resource, ServicesInfoParams, SignaturePolicy,
+    SystemClock,
+};
 use adobededrmtools_crypto::init_rand as inner_init_rand;
 use anyhow::Context;
 use clap::Parser;
 
 use requests::ResourceDownloader;
 
+const ACSM_EXPIRATION_WARNING: Duration = Duration::from_secs(60 * 60);
+
 fn init_logger() {
     env_logger::init();
 }
@@ -17,11 +25,36 @@ fn init_rand() {
     inner_init_rand(seed);
 }
 
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum OnActivationLimit {
+    /// Fail with guidance
+    Stop,
+    /// Activate the account's device again
+    ReuseDevice,
+    /// Switch to a new anonymous account, archiving the current one
+    Rotate,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum LicenseSignatures {
+    /// Fail when a license token doesn't match its signature
+    Enforce,
+    /// Warn when a license token doesn't match its signature
+    Warn,
+    /// Don't check the signatures
+    Skip,
+}
+
 #[derive(clap::Parser)]
 #[command(version, about, long_about = None, name = "adobededrmtools")]
 struct Cli {
-    #[arg(long, help = "Path to .acsm file")]
-    acsm: String,
+    #[arg(
+        long,
+        required_unless_present_any = ["export_adobe_key", "download_again", "user_info"],
+        conflicts_with = "download_again",
+        help = "Path to .acsm file"
+    )]
+    acsm: Option<String>,
 
     #[arg(
         long,
@@ -36,6 +69,65 @@ struct Cli {
         help = "Path to directory to write the output resources to"
     )]
     out: String,
+
+    #[arg(
+        long,
+        help = "Path to write the private license key to, as adobekey.der for DeDRM_tools"
+    )]
+    export_adobe_key: Option<String>,
+
+    #[arg(
+        long,
+        help = "Path to resource_N.json written by an earlier fulfillment, to download the resource again"
+    )]
+    download_again: Option<String>,
+
+    #[arg(
+        long,
+        help = "Print the account's user as known to the activation service"
+    )]
+    user_info: bool,
+
+    #[arg(
+        long,
+        help = "Id of the account's device to fulfill as, the first device by default"
+    )]
+    device: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "stop",
+        help = "What to do when the account has reached the activation limit"
+    )]
+    on_activation_limit: OnActivationLimit,
+
+    #[arg(
+        long,
+        default_value = "archived-accounts",
+        help = "Path to directory to keep the accounts rotated away from in"
+    )]
+    account_archive: String,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "warn",
+        help = "How to check the signatures of the license tokens against the license service"
+    )]
+    license_signatures: LicenseSignatures,
+
+    #[arg(
+        long,
+        help = "Path to JSON file to cache the Adobe services info and license service certificates in"
+    )]
+    services_cache: Option<String>,
+
+    #[arg(
+        long,
+        help = "Fail instead of querying the Adobe services info when it is not cached"
+    )]
+    offline: bool,
 }
 
 #[tokio::main]
@@ -47,6 +139,15 @@ async fn main() -> anyhow::Result<()> {
         acsm,
         account: account_path,
         out: out_directory,
+        export_adobe_key,
+        download_again,
+        user_info,
+        device,
+        on_activation_limit,
+        account_archive,
+        license_signatures,
+        services_cache,
+        offline,
     } = Cli::parse();
 
     let out_directory = std::path::Path::new(&out_directory);
@@ -63,21 +164,65 @@ async fn main() -> anyhow::Result<()> {
         Ok(())
     };
 
+    // Load .acsm file. It's checked before anything touches the network.
+    let acsm = acsm
+        .map(|path| Acsm::from_file(&path).context("could not read acsm file"))
+        .transpose()?;
+    if let Some(acsm) = &acsm {
+        if let Some(title) = acsm.title() {
+            match acsm.creator() {
+                Some(creator) => println!("ACSM is for \"{}\" by {}", title, creator),
+                None => println!("ACSM is for \"{}\"", title),
+            }
+        }
+
+        acsm.check_expiration(&SystemClock)?;
+        if let Some(expiration) = acsm.expiration()
+            && acsm.expires_within(&SystemClock, ACSM_EXPIRATION_WARNING)
+        {
+            println!(
+                "Warning: the ACSM expires at {}, it has to be fulfilled before then",
+                expiration
+            );
+        }
+    }
+
     let http_client = requests::ReqwestHttpClient;
     let resource_downloader = requests::ReqwestResourceDownloader;
+    let license_signature_policy = match license_signatures {
+        LicenseSignatures::Enforce => SignaturePolicy::Enforce,
+        LicenseSignatures::Warn => SignaturePolicy::Warn,
+        LicenseSignatures::Skip => SignaturePolicy::Skip,
+    };
+    let services_info = ServicesInfoParams {
+        cache: services_cache.map(|path| Arc::new(FileServicesInfoCache::new(path)) as _),
+        offline,
+        ..Default::default()
+    };
 
     // Load existing account or create a new one.
-    let account = if let Ok(account_file) = std::fs::File::open(&account_path) {
+    let mut account = if let Ok(account_file) = std::fs::File::open(&account_path) {
         let account: AdobeAccount = serde_json::from_reader(account_file)?;
         println!(
             "Using account loaded from file: {}",
             account.user_credentials.user
         );
         account
+    } else if acsm.is_none() {
+        // Exporting a key, querying the user or downloading a resource again only makes sense
+        // for an existing account.
+        return Err(anyhow::anyhow!(
+            "no stored account was found at {}, accounts are only created to fulfill an acsm",
+            account_path
+        ));
     } else {
         println!("No stored account was found. Creating a new Adobe account..");
 
-        let account = adobededrmtools::create_adobe_account(&http_client, Default::default())
+        let params = CreateAccountParams {
+            services_info: services_info.clone(),
+            ..Default::default()
+        };
+        let account = adobededrmtools::create_adobe_account(&http_client, params)
             .await
             .context("could not create adobe account")?;
 
@@ -86,21 +231,107 @@ async fn main() -> anyhow::Result<()> {
         account
     };
 
-    // Load .acsm file.
-    let acsm = Acsm::from_file(&acsm).context("could not read acsm file")?;
+    if let Some(adobe_key_path) = export_adobe_key {
+        let adobe_key =
+            adobededrmtools::dedrm::export_adobe_key(&account.user_credentials.private_license_key)
+                .context("could not export adobe key")?;
+        std::fs::write(&adobe_key_path, adobe_key).context("could not write adobe key")?;
+        println!("Private license key was exported to {}", adobe_key_path);
+    }
+
+    if user_info {
+        let user_info = adobededrmtools::get_user_info(&http_client, &account, &services_info)
+            .await
+            .context("could not get user info")?;
+        match &user_info.username {
+            Some(username) => println!(
+                "User {} signed in with {} as {}",
+                user_info.user, user_info.sign_in_method, username
+            ),
+            None => println!("User {} is anonymous", user_info.user),
+        }
+        if let (Some(count), Some(max_count)) =
+            (user_info.activation_count, user_info.max_activation_count)
+        {
+            println!("The user has {} of {} activations", count, max_count);
+        }
+    }
+
+    let result = if let Some(acsm) = acsm {
+        let params = FulfillParams {
+            activation_limit_policy: match on_activation_limit {
+                OnActivationLimit::Stop => ActivationLimitPolicy::Stop,
+                OnActivationLimit::ReuseDevice => ActivationLimitPolicy::ReuseDevice(
+                    account.device(device.as_deref())?.device_info.clone(),
+                ),
+                OnActivationLimit::Rotate => ActivationLimitPolicy::RotateAnonymous,
+            },
+            account_archive: Some(Arc::new(DirectoryAccountArchive::new(account_archive))),
+            license_signature_policy,
+            services_info,
+            device,
+            ..Default::default()
+        };
+        let user = account.user_credentials.user.clone();
+
+        // Fulfill ACSM.
+        println!("Fulfilling ACSM..");
+        let result = adobededrmtools::fulfill_acsm(&http_client, &acsm, &mut account, params)
+            .await
+            .context("failed to fulfill acsm");
+
+        if account.user_credentials.user != user {
+            println!(
+                "Activation limit reached, switched to new account {}. The previous account was archived.",
+                account.user_credentials.user
+            );
+        }
+        result
+    } else if let Some(resource_path) = download_again {
+        let resource: Resource = serde_json::from_reader(
+            std::fs::File::open(&resource_path).context("could not open resource file")?,
+        )
+        .context("could not read resource file")?;
 
-    // Fulfill ACSM.
-    println!("Fulfilling ACSM..");
-    let resources = adobededrmtools::fulfill_acsm(&http_client, &acsm, &account)
+        println!("Downloading resource {} again..", resource.resource);
+        adobededrmtools::download_resource_again(
+            &http_client,
+            &mut account,
+            &resource,
+            FulfillParams {
+                license_signature_policy,
+                services_info,
+                device,
+                ..Default::default()
+            },
+        )
         .await
-        .context("failed to fulfill acsm")?;
+        .map(|resource| vec![resource])
+        .context("failed to download resource again")
+    } else {
+        return Ok(());
+    };
+
+    // The account records the operators it has authenticated with.
+    serde_json::to_writer_pretty(std::fs::File::create(&account_path)?, &account)?;
+    let resources = result?;
 
     log::debug!("resources: {:?}", resources);
     println!("Fulfill returned {} resource", resources.len());
 
     for (i, resource) in resources.into_iter().enumerate() {
         let i = i + 1;
+
+        // The license data allows to download the resource again with --download-again.
+        write_out_directory(
+            &format!("resource_{}.json", i),
+            &serde_json::to_vec_pretty(&resource)?,
+        )?;
+
         println!("Downloading resource #{}: {:?}", i, resource.download);
+        if let Some(expiration) = resource.expiration() {
+            println!("The license of resource #{} expires at {}", i, expiration);
+        }
 
         let downloaded = resource_downloader
             .download_resource(&resource.download)
@@ -134,7 +365,7 @@ async fn main() -> anyhow::Result<()> {
                 println!("Could not remove DRM from the resource: {:?}", err);
                 println!("Storing raw resource.");
 
-                write_out_directory(&format!("resource_{}.raw", i), &encrypted_resource)?;
+                write_out_directory(&format!("resource_{}.raw", i), encrypted_resource)?;
             }
         }
     }
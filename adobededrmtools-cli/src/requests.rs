@@ -7,7 +7,7 @@ impl http_client::HttpClient for ReqwestHttpClient {
     async fn request(
         &self,
         request: http_client::HttpRequest,
-    ) -> anyhow::Result<http_client::HttpResponse> {
+    ) -> Result<http_client::HttpResponse, http_client::HttpError> {
         let method = match request.method {
             http_client::HttpMethod::Get => reqwest::Method::GET,
             http_client::HttpMethod::Post => reqwest::Method::POST,
@@ -23,10 +23,7 @@ impl http_client::HttpClient for ReqwestHttpClient {
                 .body(content.content);
         }
 
-        let response = request_builder
-            .send()
-            .await
-            .context("reqwest request send failed")?;
+        let response = request_builder.send().await?;
 
         let content_type = response
             .headers()
@@ -37,10 +34,7 @@ impl http_client::HttpClient for ReqwestHttpClient {
 
         let response_code = response.status().as_u16();
 
-        let body = response
-            .bytes()
-            .await
-            .context("failed to read response body")?;
+        let body = response.bytes().await?;
 
         Ok(http_client::HttpResponse {
             response_code,
@@ -1,7 +1,10 @@
 use super::rand_bytes;
 
-use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit, block_padding::Pkcs7};
-use anyhow::Context;
+use crate::error::{Context, Error, Result};
+use aes::cipher::{
+    BlockDecryptMut, BlockEncryptMut, KeyIvInit,
+    block_padding::{Pkcs7, UnpadError},
+};
 
 type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
 type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
@@ -11,9 +14,9 @@ fn aes128_cbc_encrypt(key: &[u8], iv: &[u8], plaintext: &[u8]) -> Vec<u8> {
     encryptor.encrypt_padded_vec_mut::<Pkcs7>(plaintext)
 }
 
-fn aes128_cbc_decrypt(key: &[u8], iv: &[u8], ciphertext: &[u8]) -> anyhow::Result<Vec<u8>> {
+fn aes128_cbc_decrypt(key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, UnpadError> {
     let decryptor = Aes128CbcDec::new(key.into(), iv.into());
-    Ok(decryptor.decrypt_padded_vec_mut::<Pkcs7>(ciphertext)?)
+    decryptor.decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
 }
 
 const IV_LEN: usize = 16;
@@ -32,9 +35,12 @@ pub fn encrypt_aes(key: &[u8; 16], data: &[u8]) -> Vec<u8> {
     output
 }
 
-pub fn decrypt_aes(key: &[u8; 16], data: &[u8]) -> anyhow::Result<Vec<u8>> {
+pub fn decrypt_aes(key: &[u8; 16], data: &[u8]) -> Result<Vec<u8>> {
     if data.len() < IV_LEN {
-        return Err(anyhow::anyhow!("ciphertext is too short: {}", data.len()));
+        return Err(Error::Invalid(format!(
+            "ciphertext is too short: {}",
+            data.len()
+        )));
     }
 
     let iv = &data[..IV_LEN];
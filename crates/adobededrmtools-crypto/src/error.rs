@@ -0,0 +1,38 @@
+use std::error::Error as StdError;
+
+/// Error of a cryptographic operation.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid base64: {0}")]
+    Base64(#[from] base64::DecodeError),
+    /// The underlying implementation rejected the input.
+    #[error("{context}: {source}")]
+    Backend {
+        context: String,
+        #[source]
+        source: Box<dyn StdError + Send + Sync>,
+    },
+    #[error("{0}")]
+    Invalid(String),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+pub(crate) trait Context<T> {
+    fn context(self, context: &str) -> Result<T>;
+}
+
+impl<T, E: StdError + Send + Sync + 'static> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: &str) -> Result<T> {
+        self.map_err(|err| Error::Backend {
+            context: context.to_string(),
+            source: Box::new(err),
+        })
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context(self, context: &str) -> Result<T> {
+        self.ok_or_else(|| Error::Invalid(context.to_string()))
+    }
+}
@@ -1,5 +1,6 @@
 mod aes;
 mod b64;
+mod error;
 mod pkcs12;
 mod pkey;
 mod rand;
@@ -9,9 +10,10 @@ mod signer;
 
 pub use aes::{decrypt_aes, encrypt_aes};
 pub use b64::{b64, unb64};
-pub use pkcs12::{ParsedPkcs12, parse_pkcs12};
+pub use error::{Error, Result};
+pub use pkcs12::{ParsedPkcs12, make_pkcs12, parse_pkcs12};
 pub use pkey::Pkey;
 pub use rand::{init_rand, rand_bytes};
-pub use rsa::{encrypt_with_cert, make_keypair};
+pub use rsa::{encrypt_with_cert, make_keypair, verify_with_cert};
 pub use sha1::Sha1;
 pub use signer::{Signer, make_signer};
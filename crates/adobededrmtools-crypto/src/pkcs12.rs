@@ -1,11 +1,13 @@
-use anyhow::Context;
+use crate::error::{Context, Error, Result};
+
+use super::Sha1;
 
 pub struct ParsedPkcs12 {
     pub pkey: Vec<u8>,
     pub cert: Vec<u8>,
 }
 
-pub fn parse_pkcs12(pkcs12: &[u8], password: &str) -> anyhow::Result<ParsedPkcs12> {
+pub fn parse_pkcs12(pkcs12: &[u8], password: &str) -> Result<ParsedPkcs12> {
     let ks = p12_keystore::KeyStore::from_pkcs12(pkcs12, password)
         .context("could not parse pkcs12 keystore")?;
 
@@ -15,8 +17,8 @@ pub fn parse_pkcs12(pkcs12: &[u8], password: &str) -> anyhow::Result<ParsedPkcs1
 
     let key = chain.key();
     if chain.chain().len() != 1 {
-        return Err(anyhow::anyhow!(
-            "expected chain of length 1 in pkcs12 keystore"
+        return Err(Error::Invalid(
+            "expected chain of length 1 in pkcs12 keystore".to_string(),
         ));
     }
 
@@ -27,3 +29,29 @@ pub fn parse_pkcs12(pkcs12: &[u8], password: &str) -> anyhow::Result<ParsedPkcs1
         cert: cert.as_der().to_vec(),
     })
 }
+
+/// Builds a password-protected PKCS#12 keystore from a PKCS#8 private key and its certificate.
+/// Uses the legacy algorithms, since ADE-based readers don't support the modern ones.
+pub fn make_pkcs12(pkey: &[u8], cert: &[u8], password: &str) -> Result<Vec<u8>> {
+    let cert = p12_keystore::Certificate::from_der(cert).context("could not parse certificate")?;
+
+    let mut hasher = Sha1::new();
+    hasher.update(cert.as_der());
+    let local_key_id = hasher.finalize();
+
+    let mut ks = p12_keystore::KeyStore::new();
+    ks.add_entry(
+        "user",
+        p12_keystore::KeyStoreEntry::PrivateKeyChain(p12_keystore::PrivateKeyChain::new(
+            pkey,
+            local_key_id,
+            [cert],
+        )),
+    );
+
+    ks.writer(password)
+        .encryption_algorithm(p12_keystore::EncryptionAlgorithm::PbeWithShaAnd3KeyTripleDesCbc)
+        .mac_algorithm(p12_keystore::MacAlgorithm::HmacSha1)
+        .write()
+        .context("could not write pkcs12 keystore")
+}
@@ -1,18 +1,44 @@
 use super::rand::rng;
-use anyhow::Context;
-use rsa::pkcs8::DecodePrivateKey;
+use crate::error::{Context, Result};
+use rsa::pkcs1::{DecodeRsaPrivateKey, EncodeRsaPrivateKey};
+use rsa::pkcs8::{DecodePrivateKey, EncodePrivateKey};
 
 #[derive(Clone)]
 pub struct Pkey(rsa::RsaPrivateKey);
 
 impl Pkey {
-    pub fn from_der(pkey_der: &[u8]) -> anyhow::Result<Self> {
+    pub fn from_der(pkey_der: &[u8]) -> Result<Self> {
         Ok(Self(
             rsa::RsaPrivateKey::from_pkcs8_der(pkey_der)
                 .context("could not parse rsa pkey from pkcs8 der")?,
         ))
     }
 
+    pub fn from_pkcs1_der(pkey_der: &[u8]) -> Result<Self> {
+        Ok(Self(
+            rsa::RsaPrivateKey::from_pkcs1_der(pkey_der)
+                .context("could not parse rsa pkey from pkcs1 der")?,
+        ))
+    }
+
+    pub fn to_pkcs8_der(&self) -> Result<Vec<u8>> {
+        Ok(self
+            .0
+            .to_pkcs8_der()
+            .context("could not encode rsa pkey as pkcs8 der")?
+            .as_bytes()
+            .to_vec())
+    }
+
+    pub fn to_pkcs1_der(&self) -> Result<Vec<u8>> {
+        Ok(self
+            .0
+            .to_pkcs1_der()
+            .context("could not encode rsa pkey as pkcs1 der")?
+            .as_bytes()
+            .to_vec())
+    }
+
     pub fn sign(&self, data: &[u8]) -> Vec<u8> {
         let mut rng = rng();
 
@@ -21,10 +47,9 @@ impl Pkey {
             .expect("could not sign rsa")
     }
 
-    pub fn decrypt(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
-        Ok(self
-            .0
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        self.0
             .decrypt(rsa::Pkcs1v15Encrypt, data)
-            .context("could not decrypt rsa")?)
+            .context("could not decrypt rsa")
     }
 }
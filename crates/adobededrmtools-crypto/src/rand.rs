@@ -14,7 +14,6 @@ static INITIAL_SEED: OnceLock<[u8; 32]> = OnceLock::new();
 pub fn init_rand(initial_seed: [u8; 32]) {
     INITIAL_SEED
         .set(initial_seed)
-        .ok()
         .expect("cannot initialize seed multiple times");
 }
 
@@ -36,7 +35,8 @@ impl RsaRngCore for RngRef {
     }
 
     fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rsa::rand_core::Error> {
-        Ok(self.0.borrow_mut().fill_bytes(dest))
+        self.0.borrow_mut().fill_bytes(dest);
+        Ok(())
     }
 }
 
@@ -1,4 +1,4 @@
-use anyhow::Context;
+use crate::error::{Context, Result};
 use rsa::pkcs1::DecodeRsaPublicKey;
 use rsa::pkcs8::{EncodePrivateKey, EncodePublicKey};
 use x509_cert::der::Decode;
@@ -22,22 +22,35 @@ pub fn make_keypair() -> (Vec<u8>, Vec<u8>) {
     (pubkey_der, privkey_der)
 }
 
-pub fn encrypt_with_cert(cert_der: &[u8], plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+fn cert_public_key(cert_der: &[u8]) -> Result<rsa::RsaPublicKey> {
     let cert = x509_cert::certificate::Certificate::from_der(cert_der)
         .context("could not parse X.509 certificate from DER")?;
 
-    let pubkey = rsa::RsaPublicKey::from_pkcs1_der(
+    rsa::RsaPublicKey::from_pkcs1_der(
         cert.tbs_certificate
             .subject_public_key_info
             .subject_public_key
             .raw_bytes(),
     )
     .ok()
-    .context("could not parse RSA public key from DER")?;
+    .context("could not parse RSA public key from DER")
+}
+
+pub fn encrypt_with_cert(cert_der: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let pubkey = cert_public_key(cert_der)?;
 
     let mut rng = rng();
-    Ok(pubkey
+    pubkey
         .encrypt(&mut rng, rsa::Pkcs1v15Encrypt, plaintext)
         .ok()
-        .context("could not encrypt RSA")?)
+        .context("could not encrypt RSA")
+}
+
+/// Checks a signature made with [`Signer`](crate::Signer), i.e. over an unprefixed digest,
+/// against the certificate's public key.
+pub fn verify_with_cert(cert_der: &[u8], digest: &[u8], signature: &[u8]) -> Result<bool> {
+    let pubkey = cert_public_key(cert_der)?;
+    Ok(pubkey
+        .verify(rsa::Pkcs1v15Sign::new_unprefixed(), digest, signature)
+        .is_ok())
 }
@@ -2,6 +2,12 @@ use sha1::Digest;
 
 pub struct Sha1(::sha1::Sha1);
 
+impl Default for Sha1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Sha1 {
     pub fn new() -> Self {
         Self(::sha1::Sha1::new())
@@ -1,4 +1,4 @@
-use anyhow::Context;
+use crate::error::{Context, Result};
 
 use super::{Pkey, b64};
 
@@ -14,7 +14,7 @@ impl Signer {
     }
 }
 
-pub fn make_signer(private_auth_key: &[u8]) -> anyhow::Result<Signer> {
+pub fn make_signer(private_auth_key: &[u8]) -> Result<Signer> {
     let pkey = Pkey::from_der(private_auth_key).context("could not construct user pkey")?;
     Ok(Signer::new(pkey))
 }
@@ -0,0 +1,289 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::serializarion::serde_base64;
+use crate::{ActivatedDevice, DeviceIdentity, DeviceInfo, Error, UserCredentials, error::Context};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdobeMinServicesInfo {
+    pub activation_url: String,
+    pub auth_url: String,
+    #[serde(with = "serde_base64")]
+    pub auth_certificate: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_info_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "StoredAdobeAccount")]
+pub struct AdobeAccount {
+    pub services: AdobeMinServicesInfo,
+    pub user_credentials: UserCredentials,
+    /// Devices the user is activated on. The first one is used unless another one is picked.
+    pub devices: Vec<AccountDevice>,
+    /// Operators the account has authenticated and initialized the license service for.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authorized_operators: Vec<AuthorizedOperator>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountDevice {
+    pub device_info: DeviceInfo,
+    pub activation_token: ActivatedDevice,
+    /// Serial and key the fingerprint is derived from, written to `device.xml` and `devicesalt`
+    /// when the device is exported. Unknown for devices activated with a given fingerprint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<DeviceIdentity>,
+}
+
+impl AccountDevice {
+    /// Device id assigned by the activation service, `urn:uuid:...`.
+    pub fn id(&self) -> &str {
+        &self.activation_token.device
+    }
+}
+
+/// Account as stored by any version, before the single device fields were replaced by `devices`.
+#[derive(Deserialize)]
+struct StoredAdobeAccount {
+    services: AdobeMinServicesInfo,
+    user_credentials: UserCredentials,
+    #[serde(default)]
+    devices: Vec<AccountDevice>,
+    #[serde(default)]
+    authorized_operators: Vec<AuthorizedOperator>,
+    device_info: Option<DeviceInfo>,
+    activated_device: Option<String>,
+    activation_token: Option<ActivatedDevice>,
+}
+
+impl TryFrom<StoredAdobeAccount> for AdobeAccount {
+    type Error = Error;
+
+    fn try_from(stored: StoredAdobeAccount) -> crate::Result<Self> {
+        let mut devices = stored.devices;
+
+        if let Some(device_info) = stored.device_info {
+            // Accounts created before the activation token was stored only have the device id,
+            // the rest of the token is known except for the signature.
+            let activation_token = match (stored.activation_token, stored.activated_device) {
+                (Some(token), _) => token,
+                (None, Some(device)) => ActivatedDevice {
+                    device,
+                    fingerprint: device_info.fingerprint.clone(),
+                    device_type: device_info.device_type.clone(),
+                    activation_url: stored.services.activation_url.clone(),
+                    user: stored.user_credentials.user.clone(),
+                    signature: String::new(),
+                },
+                (None, None) => {
+                    return Err(Error::InvalidData(
+                        "account has device info but no activated device".to_string(),
+                    ));
+                }
+            };
+
+            devices.insert(
+                0,
+                AccountDevice {
+                    device_info,
+                    activation_token,
+                    identity: None,
+                },
+            );
+        }
+
+        if devices.is_empty() {
+            return Err(Error::InvalidData(
+                "account has no activated device".to_string(),
+            ));
+        }
+
+        Ok(AdobeAccount {
+            services: stored.services,
+            user_credentials: stored.user_credentials,
+            devices,
+            authorized_operators: stored.authorized_operators,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorizedOperator {
+    pub operator_url: String,
+    pub authorized_at: DateTime<Utc>,
+}
+
+impl AdobeAccount {
+    /// Device to act as, picked by its id. The first device is used if `device` is `None`.
+    pub fn device(&self, device: Option<&str>) -> crate::Result<&AccountDevice> {
+        match device {
+            Some(id) => self
+                .devices
+                .iter()
+                .find(|x| x.id() == id)
+                .with_context(|| format!("account has no device {}", id)),
+            None => self
+                .devices
+                .first()
+                .context("account has no activated device"),
+        }
+    }
+
+    /// Records the activation of a device, replacing an earlier one of the same device.
+    pub(crate) fn set_device(&mut self, mut device: AccountDevice, primary: bool) {
+        // A device activated again keeps the identity its fingerprint was derived from.
+        if device.identity.is_none() {
+            device.identity = self
+                .devices
+                .iter()
+                .find(|x| x.device_info.fingerprint == device.device_info.fingerprint)
+                .and_then(|x| x.identity.clone());
+        }
+        self.devices.retain(|x| {
+            x.id() != device.id() && x.device_info.fingerprint != device.device_info.fingerprint
+        });
+        if primary {
+            self.devices.insert(0, device);
+        } else {
+            self.devices.push(device);
+        }
+    }
+
+    pub(crate) fn is_operator_authorized(
+        &self,
+        operator_url: &str,
+        now: DateTime<Utc>,
+        ttl: Duration,
+    ) -> bool {
+        self.authorized_operators.iter().any(|x| {
+            x.operator_url == operator_url
+                && (now - x.authorized_at)
+                    .to_std()
+                    .is_ok_and(|elapsed| elapsed < ttl)
+        })
+    }
+
+    pub(crate) fn set_operator_authorized(
+        &mut self,
+        operator_url: &str,
+        authorized_at: Option<DateTime<Utc>>,
+    ) {
+        self.authorized_operators
+            .retain(|x| x.operator_url != operator_url);
+        if let Some(authorized_at) = authorized_at {
+            self.authorized_operators.push(AuthorizedOperator {
+                operator_url: operator_url.to_string(),
+                authorized_at,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AdobeAccount;
+    use crate::DeviceIdentity;
+    use crate::testing::{DEVICE, FINGERPRINT, test_account, test_device};
+
+    // Account as stored before it could hold several devices.
+    const LEGACY_ACCOUNT: &str = r#"{
+  "services": {
+    "activation_url": "https://adeactivate.adobe.com/adept",
+    "auth_url": "https://adeactivate.adobe.com/adept",
+    "auth_certificate": ""
+  },
+  "user_credentials": {
+    "user": "urn:uuid:4b4e7bd5-4b2b-4bb5-a2a4-3a0e5f0cbd2b",
+    "private_auth_key": "",
+    "user_certificate": "",
+    "private_license_key": "",
+    "license_certificate": ""
+  },
+  "device_info": {
+    "software_version": "10.0.4",
+    "client_os": "Windows 8",
+    "client_locale": "en",
+    "client_version": "com.adobe.adobedigitaleditions.exe v4.5.11.187303",
+    "device_type": "standalone",
+    "fingerprint": "ZmluZ2VycHJpbnQ="
+  },
+  "activated_device": "urn:uuid:00000000-0000-0000-0000-000000000001"
+}"#;
+
+    #[test]
+    fn test_load_legacy_account() {
+        let account: AdobeAccount =
+            serde_json::from_str(LEGACY_ACCOUNT).expect("could not load legacy account");
+
+        let device = account.device(None).expect("no device");
+        assert_eq!(device.id(), DEVICE);
+        assert_eq!(device.activation_token.fingerprint, FINGERPRINT);
+        assert_eq!(
+            device.activation_token.user,
+            "urn:uuid:4b4e7bd5-4b2b-4bb5-a2a4-3a0e5f0cbd2b"
+        );
+        assert_eq!(
+            account
+                .device(Some(DEVICE))
+                .unwrap()
+                .device_info
+                .fingerprint,
+            FINGERPRINT
+        );
+        assert!(account.device(Some("urn:uuid:unknown")).is_err());
+
+        // Stored again, the account only has the new fields and loads the same.
+        let stored = serde_json::to_value(&account).unwrap();
+        assert!(stored.get("device_info").is_none());
+        let reloaded: AdobeAccount = serde_json::from_value(stored).unwrap();
+        assert_eq!(reloaded.devices.len(), 1);
+        assert_eq!(reloaded.device(None).unwrap().id(), device.id());
+    }
+
+    #[test]
+    fn test_store_account_devices() {
+        let mut account = test_account();
+        let mut second = test_device("urn:uuid:00000000-0000-0000-0000-000000000002", "c2Vjb25k");
+        second.identity = Some(DeviceIdentity::generate("Reader"));
+        account.set_device(second.clone(), false);
+
+        let reloaded: AdobeAccount =
+            serde_json::from_value(serde_json::to_value(&account).unwrap()).unwrap();
+        let ids: Vec<_> = reloaded.devices.iter().map(|x| x.id()).collect();
+        assert_eq!(
+            ids,
+            [DEVICE, "urn:uuid:00000000-0000-0000-0000-000000000002"]
+        );
+        let selected = reloaded
+            .device(Some(second.id()))
+            .expect("no second device");
+        assert_eq!(selected.device_info.fingerprint, "c2Vjb25k");
+        assert_eq!(
+            selected.identity.as_ref().unwrap().device_key,
+            second.identity.as_ref().unwrap().device_key
+        );
+        assert_eq!(reloaded.device(None).unwrap().id(), DEVICE);
+        assert!(reloaded.device(None).unwrap().identity.is_none());
+
+        // Activating a device again replaces its earlier activation, keeping its identity.
+        let mut reactivated = second.clone();
+        reactivated.activation_token.device =
+            "urn:uuid:00000000-0000-0000-0000-000000000003".into();
+        reactivated.identity = None;
+        account.set_device(reactivated, true);
+        assert_eq!(account.devices.len(), 2);
+        let primary = account.device(None).unwrap();
+        assert_eq!(
+            primary.id(),
+            "urn:uuid:00000000-0000-0000-0000-000000000003"
+        );
+        assert_eq!(
+            primary.identity.as_ref().unwrap().device_serial,
+            second.identity.as_ref().unwrap().device_serial
+        );
+        assert!(account.device(Some(second.id())).is_err());
+    }
+}
@@ -1,8 +1,9 @@
 use super::{HttpClient, adept, make_expiration, random_nonce};
 use adobededrmtools_crypto::{Signer, b64, rand_bytes};
-use anyhow::Context;
 use serde::{Deserialize, Serialize};
 
+use crate::{AdeptStep, error::StepContext};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeviceInfo {
     pub software_version: String,
@@ -26,9 +27,26 @@ impl DeviceInfo {
     }
 }
 
-#[derive(Debug)]
+/// Activation token issued by the activation service for a device.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActivatedDevice {
     pub device: String,
+    pub fingerprint: String,
+    pub device_type: String,
+    pub activation_url: String,
+    pub user: String,
+    pub signature: String,
+}
+
+fn device_info_to_activate_data(device_info: &DeviceInfo) -> adept::ActivateDeviceData {
+    adept::ActivateDeviceData {
+        software_version: device_info.software_version.clone(),
+        client_os: device_info.client_os.clone(),
+        client_locale: device_info.client_locale.clone(),
+        client_version: device_info.client_version.clone(),
+        device_type: device_info.device_type.clone(),
+        fingerprint: device_info.fingerprint.clone(),
+    }
 }
 
 pub async fn activate_device<H: HttpClient>(
@@ -37,29 +55,79 @@ pub async fn activate_device<H: HttpClient>(
     activation_url: &str,
     user: &str,
     device_info: &DeviceInfo,
-) -> anyhow::Result<ActivatedDevice> {
-    // Activate account
+) -> crate::Result<ActivatedDevice> {
+    activate_target_device(
+        http_client,
+        signer,
+        activation_url,
+        user,
+        device_info,
+        device_info,
+    )
+    .await
+}
+
+/// Activates `target_device` for the user on behalf of `host_device`.
+///
+/// The host is the device the user is already activated on, and the target is an extra device,
+/// e.g. an e-ink reader, that gets its own activation token.
+pub async fn activate_target_device<H: HttpClient>(
+    http_client: &H,
+    signer: &Signer,
+    activation_url: &str,
+    user: &str,
+    host_device: &DeviceInfo,
+    target_device: &DeviceInfo,
+) -> crate::Result<ActivatedDevice> {
     let activation_token = adept::activate(
         http_client,
         signer,
-        &activation_url,
+        activation_url,
         adept::ActivateData {
-            software_version: device_info.software_version.clone(),
-            client_os: device_info.client_os.clone(),
-            client_locale: device_info.client_locale.clone(),
-            client_version: device_info.client_version.clone(),
-            device_type: device_info.device_type.clone(),
-            fingerprint: device_info.fingerprint.clone(),
+            device: device_info_to_activate_data(host_device),
+            target_device: device_info_to_activate_data(target_device),
             nonce: random_nonce(),
             expiration: make_expiration(),
             user: user.to_string(),
         },
     )
     .await
-    .context("activate failed")?;
+    .step(AdeptStep::Activate)?;
 
     log::debug!("activation token: {:?}", activation_token);
     Ok(ActivatedDevice {
         device: activation_token.device,
+        fingerprint: activation_token.fingerprint,
+        device_type: activation_token.device_type,
+        activation_url: activation_token.activation_url,
+        user: activation_token.user,
+        signature: activation_token.signature,
     })
 }
+
+pub async fn deactivate_device<H: HttpClient>(
+    http_client: &H,
+    signer: &Signer,
+    activation_url: &str,
+    user: &str,
+    device_info: &DeviceInfo,
+    activated_device: &str,
+) -> crate::Result<()> {
+    adept::deactivate(
+        http_client,
+        signer,
+        activation_url,
+        adept::DeactivateData {
+            user: user.to_string(),
+            device: activated_device.to_string(),
+            fingerprint: device_info.fingerprint.clone(),
+            device_type: device_info.device_type.clone(),
+            nonce: random_nonce(),
+            expiration: make_expiration(),
+        },
+    )
+    .await
+    .step(AdeptStep::Deactivate)?;
+
+    Ok(())
+}
@@ -0,0 +1,382 @@
+use std::path::PathBuf;
+
+use adobededrmtools_crypto::Signer;
+
+use crate::{
+    AccountDevice, AdobeAccount, CreateAccountParams, DeviceInfo, Error, FulfillParams, HttpClient,
+    SignInMethod, activation::activate_device, create_adobe_account, error::Context, make_signer,
+};
+
+/// What to do when the user has activated the maximum number of devices.
+#[derive(Debug, Clone, Default)]
+pub enum ActivationLimitPolicy {
+    /// Fail with [`Error::ActivationLimit`](crate::Error::ActivationLimit).
+    #[default]
+    Stop,
+    /// Activate the user again as `device`. Adobe doesn't take another activation slot for a
+    /// device the user has already activated, so this should be the device info used before.
+    ReuseDevice(DeviceInfo),
+    /// Switch to a new anonymous account when fulfilling. The previous account is kept in the
+    /// [`AccountArchive`] first, so the resources fulfilled with it can still be decrypted.
+    ///
+    /// Creating an account fails as with [`Stop`](Self::Stop), since the account would belong
+    /// to another user than the one signed in to.
+    RotateAnonymous,
+}
+
+/// Storage for the accounts rotated away from with [`ActivationLimitPolicy::RotateAnonymous`].
+pub trait AccountArchive: Send + Sync {
+    fn store(&self, account: &AdobeAccount) -> crate::Result<()>;
+}
+
+/// Keeps each account in its own JSON file in a directory, named after the user.
+#[derive(Debug)]
+pub struct DirectoryAccountArchive {
+    dir: PathBuf,
+}
+
+impl DirectoryAccountArchive {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl AccountArchive for DirectoryAccountArchive {
+    fn store(&self, account: &AdobeAccount) -> crate::Result<()> {
+        std::fs::create_dir_all(&self.dir).context("could not create account archive")?;
+
+        let user = &account.user_credentials.user;
+        let name = user.strip_prefix("urn:uuid:").unwrap_or(user);
+        let path = self.dir.join(format!("account-{}.json", name));
+
+        std::fs::write(&path, serde_json::to_vec_pretty(account)?)
+            .with_context(|| format!("could not write archived account to {}", path.display()))
+    }
+}
+
+/// How the user can go on after reaching the activation limit.
+pub(crate) enum LimitRecovery {
+    /// The user was activated again as a device it had activated before.
+    Reused(Box<AccountDevice>),
+    /// The caller should switch to a new anonymous user. Holds the activation limit error.
+    Rotate(Error),
+}
+
+/// Applies `policy` to `err`, an error of activating `user`.
+///
+/// Errors other than the activation limit are returned as they are.
+pub(crate) async fn recover_activation_limit<H: HttpClient>(
+    http_client: &H,
+    signer: &Signer,
+    activation_url: &str,
+    user: &str,
+    policy: &ActivationLimitPolicy,
+    err: Error,
+) -> crate::Result<LimitRecovery> {
+    if !err.is_activation_limit() {
+        return Err(err);
+    }
+
+    match policy {
+        ActivationLimitPolicy::Stop => Err(activation_limit_error(err)),
+        ActivationLimitPolicy::ReuseDevice(device_info) => {
+            log::info!(
+                "Activation limit reached for user {}, activating as device {} again",
+                user,
+                device_info.fingerprint
+            );
+            let activated_device =
+                activate_device(http_client, signer, activation_url, user, device_info)
+                    .await
+                    .context("activate_device failed")
+                    .map_err(activation_limit_error)?;
+
+            Ok(LimitRecovery::Reused(Box::new(AccountDevice {
+                device_info: device_info.clone(),
+                activation_token: activated_device,
+                identity: None,
+            })))
+        }
+        ActivationLimitPolicy::RotateAnonymous => Ok(LimitRecovery::Rotate(err)),
+    }
+}
+
+/// Makes the account usable again according to the activation limit policy of `params`.
+/// The account is updated or replaced in place.
+///
+/// Returns the device to act as from then on.
+pub(crate) async fn recover_from_activation_limit<H: HttpClient>(
+    http_client: &H,
+    account: &mut AdobeAccount,
+    params: &FulfillParams,
+    err: Error,
+) -> crate::Result<Option<String>> {
+    let signer = make_signer(&account.user_credentials.private_auth_key)?;
+    let recovery = recover_activation_limit(
+        http_client,
+        &signer,
+        &account.services.activation_url,
+        &account.user_credentials.user,
+        &params.activation_limit_policy,
+        err,
+    )
+    .await?;
+
+    match recovery {
+        LimitRecovery::Reused(device) => {
+            let id = device.id().to_string();
+            account.set_device(*device, true);
+            Ok(Some(id))
+        }
+        LimitRecovery::Rotate(err) => {
+            // The resources fulfilled with the account can only be decrypted with its keys.
+            let Some(archive) = &params.account_archive else {
+                return Err(activation_limit_error(err))
+                    .context("cannot switch to a new account without an account archive");
+            };
+            archive
+                .store(account)
+                .context("could not archive the account")?;
+
+            let device = account.device(params.device.as_deref())?;
+            let new_account = create_adobe_account(
+                http_client,
+                CreateAccountParams {
+                    activation_url: account.services.activation_url.clone(),
+                    device_info: device.device_info.clone(),
+                    device_identity: device.identity.clone(),
+                    sign_in_method: SignInMethod::Anonymous,
+                    services_info: params.services_info.clone(),
+                    activation_limit_policy: params.activation_limit_policy.clone(),
+                },
+            )
+            .await
+            .context("could not create a new account")?;
+
+            log::info!(
+                "Activation limit reached for user {}, switched to new anonymous user {}",
+                account.user_credentials.user,
+                new_account.user_credentials.user
+            );
+            *account = new_account;
+            Ok(None)
+        }
+    }
+}
+
+/// Adds guidance to activation limit errors, leaving other errors as they are.
+pub(crate) fn activation_limit_error(err: Error) -> Error {
+    if !err.is_activation_limit() {
+        return err;
+    }
+
+    Error::ActivationLimit {
+        guidance: "deactivate one of the user's devices, or use another ActivationLimitPolicy"
+            .to_string(),
+        source: Box::new(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{AccountArchive, DirectoryAccountArchive, recover_from_activation_limit};
+    use crate::{
+        ActivationLimitPolicy, AdeptError, AdeptStep, AdobeAccount, AdobeAuthServiceInfo,
+        AdobeServicesInfo, CachedServicesInfo, Error, FulfillParams, MemoryServicesInfoCache,
+        ServicesInfoCache, ServicesInfoParams, SignInMethodInfo,
+        error::StepContext,
+        http_client::testing::MockHttpClient,
+        testing::{ADEPT_URL, USER, USER_CERTIFICATE, test_account, test_device},
+    };
+
+    fn activation_limit() -> Error {
+        let result: Result<(), _> = Err(AdeptError::new(
+            "E_ACT_TOO_MANY_ACTIVATIONS".to_string(),
+            Vec::new(),
+        ));
+        result.step(AdeptStep::Fulfill).unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn test_activation_limit_stop() {
+        let http = MockHttpClient::new();
+        let mut account = test_account();
+
+        let err = recover_from_activation_limit(
+            &http,
+            &mut account,
+            &FulfillParams::default(),
+            activation_limit(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, Error::ActivationLimit { .. }));
+        assert!(err.is_activation_limit());
+        assert!(http.urls().is_empty());
+        assert_eq!(account.devices.len(), 1);
+
+        // Other errors are returned as they are, whatever the policy.
+        let params = FulfillParams {
+            activation_limit_policy: ActivationLimitPolicy::RotateAnonymous,
+            ..Default::default()
+        };
+        let err = recover_from_activation_limit(
+            &http,
+            &mut account,
+            &params,
+            Error::Protocol("bad response".to_string()),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, Error::Protocol(_)));
+    }
+
+    #[tokio::test]
+    async fn test_activation_limit_reuse_device() {
+        let http = MockHttpClient::new();
+        let mut account = test_account();
+        let reused = test_device("urn:uuid:00000000-0000-0000-0000-000000000002", "cmV1c2Vk");
+        let params = FulfillParams {
+            activation_limit_policy: ActivationLimitPolicy::ReuseDevice(reused.device_info.clone()),
+            ..Default::default()
+        };
+
+        http.respond_error(
+            &format!("{ADEPT_URL}/Activate"),
+            "E_ACT_TOO_MANY_ACTIVATIONS",
+        );
+        let err = recover_from_activation_limit(&http, &mut account, &params, activation_limit())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ActivationLimit { .. }));
+        assert_eq!(account.devices.len(), 1);
+
+        http.respond(
+            &format!("{ADEPT_URL}/Activate"),
+            &format!(
+                "<activationToken><device>{}</device><fingerprint>cmV1c2Vk</fingerprint>\
+                 <deviceType>standalone</deviceType><activationURL>{ADEPT_URL}</activationURL>\
+                 <user>{USER}</user><signature>c2lnbmF0dXJl</signature></activationToken>",
+                reused.id()
+            ),
+        );
+        let device =
+            recover_from_activation_limit(&http, &mut account, &params, activation_limit())
+                .await
+                .expect("recover_from_activation_limit failed");
+
+        // The reused device becomes the one the account acts as by default.
+        assert_eq!(device.as_deref(), Some(reused.id()));
+        assert_eq!(account.devices.len(), 2);
+        assert_eq!(account.device(None).unwrap().id(), reused.id());
+        assert_eq!(
+            account.device(None).unwrap().device_info.fingerprint,
+            "cmV1c2Vk"
+        );
+        assert!(http.requests()[1].body.contains("cmV1c2Vk"));
+    }
+
+    #[tokio::test]
+    async fn test_activation_limit_rotate_anonymous() {
+        const AUTH_URL: &str = "https://auth.example.com/adept";
+
+        let http = MockHttpClient::new();
+        let mut account = test_account();
+
+        // Without an archive, the account is kept.
+        let params = FulfillParams {
+            activation_limit_policy: ActivationLimitPolicy::RotateAnonymous,
+            ..Default::default()
+        };
+        let err = recover_from_activation_limit(&http, &mut account, &params, activation_limit())
+            .await
+            .unwrap_err();
+        assert!(err.is_activation_limit());
+        assert!(http.urls().is_empty());
+
+        let dir = std::env::temp_dir().join(format!(
+            "adobededrmtools-rotate-archive-{}",
+            std::process::id()
+        ));
+        let cache = MemoryServicesInfoCache::default();
+        cache
+            .put(
+                ADEPT_URL,
+                CachedServicesInfo {
+                    services: AdobeServicesInfo {
+                        activation_url: ADEPT_URL.to_string(),
+                        user_info_url: ADEPT_URL.to_string(),
+                        activation_certificate: USER_CERTIFICATE.to_vec(),
+                        auth_service: AdobeAuthServiceInfo {
+                            auth_url: AUTH_URL.to_string(),
+                            auth_certificate: USER_CERTIFICATE.to_vec(),
+                            sign_in_methods: vec![SignInMethodInfo {
+                                method: "anonymous".to_string(),
+                                method_type: "anonymous".to_string(),
+                                name: "Anonymous".to_string(),
+                            }],
+                        },
+                    },
+                    fetched_at: "2020-01-01T00:00:00Z".parse().unwrap(),
+                },
+            )
+            .unwrap();
+        let params = FulfillParams {
+            activation_limit_policy: ActivationLimitPolicy::RotateAnonymous,
+            account_archive: Some(Arc::new(DirectoryAccountArchive::new(&dir))),
+            services_info: ServicesInfoParams {
+                cache: Some(Arc::new(cache)),
+                offline: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        http.respond_error(&format!("{AUTH_URL}/SignInDirect"), "E_AUTH_FAILED");
+        let err = recover_from_activation_limit(&http, &mut account, &params, activation_limit())
+            .await
+            .unwrap_err();
+        assert_eq!(err.step(), Some(AdeptStep::SignIn));
+
+        // The account is archived before signing in, with the services info of `params`.
+        let archived = std::fs::read(dir.join("account-4b4e7bd5-4b2b-4bb5-a2a4-3a0e5f0cbd2b.json"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        let archived: AdobeAccount = serde_json::from_slice(&archived.unwrap()).unwrap();
+        assert_eq!(archived.user_credentials.user, USER);
+        assert_eq!(http.urls(), [format!("{AUTH_URL}/SignInDirect")]);
+
+        // The account isn't replaced when the new one can't be created.
+        assert_eq!(account.user_credentials.user, USER);
+        assert_eq!(account.devices.len(), 1);
+    }
+
+    #[test]
+    fn test_directory_account_archive() {
+        let dir = std::env::temp_dir().join(format!(
+            "adobededrmtools-account-archive-{}",
+            std::process::id()
+        ));
+        let archive = DirectoryAccountArchive::new(dir.join("accounts"));
+
+        let mut account = test_account();
+        archive.store(&account).unwrap();
+        account.user_credentials.user = "reader".to_string();
+        archive.store(&account).unwrap();
+
+        let read = |name: &str| -> AdobeAccount {
+            serde_json::from_slice(&std::fs::read(dir.join("accounts").join(name)).unwrap())
+                .unwrap()
+        };
+        let first = read("account-4b4e7bd5-4b2b-4bb5-a2a4-3a0e5f0cbd2b.json");
+        let second = read("account-reader.json");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            first.user_credentials.user,
+            "urn:uuid:4b4e7bd5-4b2b-4bb5-a2a4-3a0e5f0cbd2b"
+        );
+        assert_eq!(second.user_credentials.user, "reader");
+    }
+}
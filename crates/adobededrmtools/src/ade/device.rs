@@ -0,0 +1,65 @@
+use adobededrmtools_crypto::{Sha1, b64, rand_bytes};
+use serde::{Deserialize, Serialize};
+
+use crate::DeviceInfo;
+use crate::serializarion::serde_base64_array;
+
+/// Identity of a device, as stored in its `device.xml` and `devicesalt`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceIdentity {
+    pub device_serial: String,
+    pub device_name: String,
+    /// Key stored in `devicesalt`. Protects the user keys in `activation.xml`.
+    #[serde(with = "serde_base64_array")]
+    pub device_key: [u8; 16],
+}
+
+impl DeviceIdentity {
+    pub fn generate(device_name: &str) -> Self {
+        let device_serial = rand_bytes::<20>()
+            .iter()
+            .map(|x| format!("{:02x}", x))
+            .collect();
+
+        Self {
+            device_serial,
+            device_name: device_name.to_string(),
+            device_key: rand_bytes(),
+        }
+    }
+
+    /// Fingerprint derived from the serial and the device key, the same way ADE does it.
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Sha1::new();
+        hasher.update(self.device_serial.as_bytes());
+        hasher.update(&self.device_key);
+        b64(&hasher.finalize())
+    }
+
+    /// Default device info, with the fingerprint of the identity.
+    pub fn device_info(&self) -> DeviceInfo {
+        DeviceInfo {
+            fingerprint: self.fingerprint(),
+            ..DeviceInfo::generate()
+        }
+    }
+}
+
+/// Extra reading device activated under an existing account.
+#[derive(Debug, Clone)]
+pub struct SecondaryDevice {
+    /// Device info sent to the activation service. The fingerprint must match the identity.
+    pub device_info: DeviceInfo,
+    pub identity: DeviceIdentity,
+}
+
+impl SecondaryDevice {
+    pub fn generate(device_name: &str) -> Self {
+        let identity = DeviceIdentity::generate(device_name);
+
+        Self {
+            device_info: identity.device_info(),
+            identity,
+        }
+    }
+}
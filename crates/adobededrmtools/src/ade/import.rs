@@ -0,0 +1,280 @@
+use std::path::Path;
+
+use adobededrmtools_crypto::{Pkey, b64, parse_pkcs12, unb64};
+
+use super::{ACTIVATION_FILE, DEVICE_FILE, DEVICE_SALT_FILE, DeviceIdentity};
+use crate::{
+    AccountDevice, AccountUsername, ActivatedDevice, AdobeAccount, AdobeMinServicesInfo,
+    DeviceInfo, UserCredentials, adept,
+};
+use crate::{Error, error::Context};
+
+/// Builds an account from the activation records in `dir`, i.e. `activation.xml`, `device.xml`
+/// and `devicesalt`. That's `~/.config/adept` for libgourou, or `.adobe-digital-editions` on
+/// an ADE-based reader.
+pub fn import_activation(dir: &Path) -> crate::Result<AdobeAccount> {
+    let read_to_string = |name: &str| {
+        std::fs::read_to_string(dir.join(name)).with_context(|| format!("could not read {}", name))
+    };
+
+    let activation_xml = read_to_string(ACTIVATION_FILE)?;
+    let device_xml = read_to_string(DEVICE_FILE)?;
+    let device_key = std::fs::read(dir.join(DEVICE_SALT_FILE))
+        .with_context(|| format!("could not read {}", DEVICE_SALT_FILE))?;
+
+    import_activation_records(&activation_xml, &device_xml, &device_key)
+}
+
+/// Builds an account from the contents of the activation records.
+pub fn import_activation_records(
+    activation_xml: &str,
+    device_xml: &str,
+    device_key: &[u8],
+) -> crate::Result<AdobeAccount> {
+    let device_key: [u8; 16] = device_key
+        .try_into()
+        .ok()
+        .with_context(|| format!("device key must be 16 bytes: {}", device_key.len()))?;
+
+    let activation =
+        adept::parse_activation_record(activation_xml).context("could not parse activation.xml")?;
+    let device = adept::parse_device_record(device_xml).context("could not parse device.xml")?;
+
+    let credentials = activation.credentials;
+    let token = activation.activation_token;
+    let service = activation.activation_service_info;
+
+    if token.user != credentials.user {
+        return Err(Error::InvalidData(format!(
+            "activation token user {} doesn't match credentials user {}",
+            token.user, credentials.user
+        )));
+    }
+    if token.fingerprint != device.fingerprint {
+        log::warn!(
+            "The activation token fingerprint and the device fingerprint don't match, but they are expected to"
+        );
+    }
+
+    // The PKCS#12 is protected with the device key, the private license key is plain PKCS#8.
+    let pkcs12 = parse_pkcs12(&unb64(&credentials.pkcs12)?, &b64(&device_key))
+        .context("could not parse pkcs12, the device key may be wrong")?;
+    let private_license_key = unb64(&credentials.private_license_key)?;
+    Pkey::from_der(&private_license_key).context("private license key is invalid")?;
+
+    let version = |name: &str| {
+        device
+            .versions
+            .iter()
+            .find(|x| x.name == name)
+            .map(|x| x.value.clone())
+    };
+    let defaults = DeviceInfo::generate();
+
+    let identity = DeviceIdentity {
+        device_serial: device.device_serial,
+        device_name: device.device_name,
+        device_key,
+    };
+    let device_info = DeviceInfo {
+        software_version: version("hobbes").unwrap_or(defaults.software_version),
+        client_os: version("clientOS").unwrap_or(defaults.client_os),
+        client_locale: version("clientLocale").unwrap_or(defaults.client_locale),
+        client_version: device.device_class,
+        device_type: device.device_type,
+        fingerprint: device.fingerprint,
+    };
+
+    Ok(AdobeAccount {
+        services: AdobeMinServicesInfo {
+            activation_url: service.activation_url,
+            auth_url: service.auth_url,
+            auth_certificate: unb64(&credentials.authentication_certificate)
+                .context("invalid authentication certificate")?,
+            user_info_url: Some(service.user_info_url),
+        },
+        user_credentials: UserCredentials {
+            user: credentials.user,
+            username: credentials.username.map(|x| AccountUsername {
+                method: x.method,
+                username: x.username,
+            }),
+            private_auth_key: pkcs12.pkey,
+            user_certificate: pkcs12.cert,
+            private_license_key,
+            license_certificate: unb64(&credentials.license_certificate)
+                .context("invalid license certificate")?,
+        },
+        devices: vec![AccountDevice {
+            device_info,
+            activation_token: ActivatedDevice {
+                device: token.device,
+                fingerprint: token.fingerprint,
+                device_type: token.device_type,
+                activation_url: token.activation_url,
+                user: token.user,
+                signature: token.signature,
+            },
+            identity: Some(identity),
+        }],
+        authorized_operators: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::import_activation;
+    use crate::ade::write_account_activation;
+    use crate::testing::USER_CERTIFICATE;
+    use crate::{
+        AccountUsername, ActivatedDevice, AdobeAccount, DeviceIdentity, SecondaryDevice,
+        SecondaryDeviceActivation, UserCredentials, write_device_activation,
+    };
+
+    /// Account linked to an Adobe ID, so the username is exported too.
+    fn test_account() -> AdobeAccount {
+        let mut account = crate::testing::test_account();
+        account.user_credentials.username = Some(AccountUsername {
+            method: "AdobeID".to_string(),
+            username: "reader@example.com".to_string(),
+        });
+        account
+    }
+
+    fn assert_same_credentials(actual: &UserCredentials, expected: &UserCredentials) {
+        assert_eq!(actual.user, expected.user);
+        assert_eq!(
+            actual.username.as_ref().map(|x| x.username.as_str()),
+            Some("reader@example.com")
+        );
+        assert_eq!(actual.private_auth_key, expected.private_auth_key);
+        assert_eq!(actual.user_certificate, expected.user_certificate);
+        assert_eq!(actual.private_license_key, expected.private_license_key);
+        assert_eq!(actual.license_certificate, expected.license_certificate);
+    }
+
+    #[test]
+    fn test_import_written_activation() {
+        let account = test_account();
+
+        let device = SecondaryDevice::generate("Reader");
+        let activation = SecondaryDeviceActivation {
+            activated_device: ActivatedDevice {
+                device: "urn:uuid:00000000-0000-0000-0000-000000000002".to_string(),
+                fingerprint: device.device_info.fingerprint.clone(),
+                device_type: device.device_info.device_type.clone(),
+                activation_url: account.services.activation_url.clone(),
+                user: account.user_credentials.user.clone(),
+                signature: "c2lnbmF0dXJl".to_string(),
+            },
+            user_info_url: "https://adeactivate.adobe.com/adept".to_string(),
+            activation_certificate: USER_CERTIFICATE.to_vec(),
+        };
+
+        let dir =
+            std::env::temp_dir().join(format!("adobededrmtools-import-{}", std::process::id()));
+        write_device_activation(&dir, &account, &device, &activation)
+            .expect("write_device_activation failed");
+        let imported = import_activation(&dir.join(".adobe-digital-editions"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        let imported = imported.expect("import_activation failed");
+
+        assert_same_credentials(&imported.user_credentials, &account.user_credentials);
+
+        assert_eq!(imported.services.auth_url, account.services.auth_url);
+        assert_eq!(
+            imported.services.auth_certificate,
+            account.services.auth_certificate
+        );
+        let imported_device = imported.device(None).expect("no imported device");
+        assert_eq!(
+            imported_device.device_info.fingerprint,
+            device.device_info.fingerprint
+        );
+        assert_eq!(imported_device.id(), activation.activated_device.device);
+    }
+
+    #[test]
+    fn test_import_written_account_activation() {
+        let mut account = test_account();
+        let dir = std::env::temp_dir().join(format!(
+            "adobededrmtools-import-account-{}",
+            std::process::id()
+        ));
+
+        // Only the picked device is exported.
+        let identity = DeviceIdentity::generate("Reader");
+        let mut second = account.devices[0].clone();
+        second.activation_token.device = "urn:uuid:00000000-0000-0000-0000-000000000002".into();
+        second.device_info = identity.device_info();
+        second.activation_token.fingerprint = identity.fingerprint();
+        second.identity = Some(identity.clone());
+        account.devices.push(second);
+
+        let written = write_account_activation(
+            &dir,
+            &account,
+            Some("urn:uuid:00000000-0000-0000-0000-000000000002"),
+            "https://adeactivate.adobe.com/adept",
+            USER_CERTIFICATE,
+        );
+        let imported = written.and_then(|()| import_activation(&dir));
+        std::fs::remove_dir_all(&dir).ok();
+        let imported = imported.expect("could not import the exported account");
+
+        assert_same_credentials(&imported.user_credentials, &account.user_credentials);
+        assert_eq!(imported.services.auth_url, account.services.auth_url);
+        assert_eq!(imported.devices.len(), 1);
+        let (expected, actual) = (
+            account
+                .device(Some("urn:uuid:00000000-0000-0000-0000-000000000002"))
+                .unwrap(),
+            imported.device(None).expect("no imported device"),
+        );
+        assert_eq!(actual.id(), expected.id());
+        assert_eq!(
+            actual.device_info.fingerprint,
+            expected.device_info.fingerprint
+        );
+        assert_eq!(
+            actual.activation_token.signature,
+            expected.activation_token.signature
+        );
+        // The device records are those the fingerprint was derived from.
+        let imported_identity = actual.identity.as_ref().expect("no imported identity");
+        assert_eq!(imported_identity.device_serial, identity.device_serial);
+        assert_eq!(imported_identity.device_key, identity.device_key);
+        assert_eq!(
+            imported_identity.fingerprint(),
+            actual.device_info.fingerprint
+        );
+
+        // Without the device key, the device records wouldn't match the fingerprint.
+        assert!(account.devices[0].identity.is_none());
+        assert!(
+            write_account_activation(
+                &dir,
+                &account,
+                None,
+                "https://adeactivate.adobe.com/adept",
+                USER_CERTIFICATE,
+            )
+            .is_err()
+        );
+
+        // Without the token signature, the exported records would be unusable.
+        account.devices[0].identity = Some(DeviceIdentity::generate("Reader"));
+        account.devices[0].activation_token.signature = String::new();
+        assert!(
+            write_account_activation(
+                &dir,
+                &account,
+                None,
+                "https://adeactivate.adobe.com/adept",
+                USER_CERTIFICATE,
+            )
+            .is_err()
+        );
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,91 @@
+use std::io::Cursor;
+
+use adobededrmtools_crypto::{Pkey, unb64};
+
+use crate::error::Context;
+use crate::{AccountUsername, adept};
+
+// ADE on macOS keeps the activation in
+// `~/Library/Application Support/Adobe/Digital Editions/activation.dat`. It's the same document
+// as activation.xml, but the private license key is stored unencrypted. Copies of it may also
+// come wrapped in a property list, either XML or binary.
+
+/// License credentials of a macOS ADE activation.
+#[derive(Debug, Clone)]
+pub struct MacActivation {
+    pub user: String,
+    pub username: Option<AccountUsername>,
+    /// PKCS#8 private license key, usable with [`dedrm_resource`](crate::dedrm::dedrm_resource).
+    pub private_license_key: Vec<u8>,
+}
+
+/// Parses `activation.dat`, or a plist with the activation document among its values.
+pub fn parse_mac_activation(data: &[u8]) -> crate::Result<MacActivation> {
+    let credentials = if is_plist(data) {
+        let plist = plist::Value::from_reader(Cursor::new(data))
+            .ok()
+            .context("could not parse plist")?;
+        find_credentials_in_plist(&plist).context("no activation document in the plist")?
+    } else {
+        let xml = std::str::from_utf8(data)
+            .ok()
+            .context("activation data is not UTF8")?;
+        adept::parse_license_credentials(xml).context("could not parse activation data")?
+    };
+
+    let private_license_key = unb64(&credentials.private_license_key)?;
+    Pkey::from_der(&private_license_key).context("private license key is invalid")?;
+
+    Ok(MacActivation {
+        user: credentials.user,
+        username: credentials.username.map(|x| AccountUsername {
+            method: x.method,
+            username: x.username,
+        }),
+        private_license_key,
+    })
+}
+
+fn is_plist(data: &[u8]) -> bool {
+    data.starts_with(b"bplist") || data.windows(6).any(|x| x == b"<plist")
+}
+
+fn find_credentials_in_plist(value: &plist::Value) -> Option<adept::LicenseCredentialsRecord> {
+    match value {
+        plist::Value::String(s) => adept::parse_license_credentials(s).ok(),
+        plist::Value::Data(d) => std::str::from_utf8(d)
+            .ok()
+            .and_then(|s| adept::parse_license_credentials(s).ok()),
+        plist::Value::Array(a) => a.iter().find_map(find_credentials_in_plist),
+        plist::Value::Dictionary(d) => d.values().find_map(find_credentials_in_plist),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_mac_activation;
+
+    #[test]
+    fn test_parse_mac_activation() {
+        let expected_key = include_bytes!("../../testdata/private_license_key.der");
+
+        for data in [
+            &include_bytes!("../../testdata/activation.dat")[..],
+            &include_bytes!("../../testdata/activation_xml.plist")[..],
+            &include_bytes!("../../testdata/activation_binary.plist")[..],
+        ] {
+            let activation = parse_mac_activation(data).expect("parse_mac_activation failed");
+
+            assert_eq!(
+                activation.user,
+                "urn:uuid:4b4e7bd5-4b2b-4bb5-a2a4-3a0e5f0cbd2b"
+            );
+            assert_eq!(
+                activation.username.as_ref().map(|x| x.username.as_str()),
+                Some("reader@example.com")
+            );
+            assert_eq!(activation.private_license_key, expected_key);
+        }
+    }
+}
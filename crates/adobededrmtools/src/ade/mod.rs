@@ -0,0 +1,18 @@
+mod device;
+mod import;
+mod mac;
+mod records;
+mod registry;
+
+pub use device::{DeviceIdentity, SecondaryDevice};
+pub use import::{import_activation, import_activation_records};
+pub use mac::{MacActivation, parse_mac_activation};
+pub use records::{SecondaryDeviceActivation, write_account_activation, write_device_activation};
+pub use registry::{
+    RegistryActivation, RegistryActivationToken, RegistryCredentials, parse_registry_activation,
+};
+
+const ADE_DIR: &str = ".adobe-digital-editions";
+const ACTIVATION_FILE: &str = "activation.xml";
+const DEVICE_FILE: &str = "device.xml";
+const DEVICE_SALT_FILE: &str = "devicesalt";
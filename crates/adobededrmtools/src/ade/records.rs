@@ -0,0 +1,177 @@
+use std::path::Path;
+
+use adobededrmtools_crypto::{b64, make_pkcs12};
+
+use super::{
+    ACTIVATION_FILE, ADE_DIR, DEVICE_FILE, DEVICE_SALT_FILE, DeviceIdentity, SecondaryDevice,
+};
+use crate::{
+    AdobeAccount, AdobeMinServicesInfo, DeviceInfo, UserCredentials, activation::ActivatedDevice,
+    adept,
+};
+use crate::{Error, error::Context};
+
+/// Result of activating a secondary device.
+#[derive(Debug, Clone)]
+pub struct SecondaryDeviceActivation {
+    pub activated_device: ActivatedDevice,
+    pub user_info_url: String,
+    pub activation_certificate: Vec<u8>,
+}
+
+struct ActivationRecord<'a> {
+    services: &'a AdobeMinServicesInfo,
+    user_info_url: &'a str,
+    activation_certificate: &'a [u8],
+    user_credentials: &'a UserCredentials,
+    device_info: &'a DeviceInfo,
+    identity: &'a DeviceIdentity,
+    activated_device: &'a ActivatedDevice,
+}
+
+fn serialize_activation_xml(record: &ActivationRecord) -> crate::Result<String> {
+    let credentials = record.user_credentials;
+    // The PKCS#12 password is the device key. The private license key is stored as is,
+    // which is what libgourou and DeDRM_tools expect.
+    let pkcs12 = make_pkcs12(
+        &credentials.private_auth_key,
+        &credentials.user_certificate,
+        &b64(&record.identity.device_key),
+    )
+    .context("could not make pkcs12")?;
+
+    adept::serialize_activation_record(adept::ActivationRecordData {
+        activation_service_info: adept::ActivationServiceInfoRecord {
+            auth_url: record.services.auth_url.clone(),
+            user_info_url: record.user_info_url.to_string(),
+            activation_url: record.services.activation_url.clone(),
+            certificate: b64(record.activation_certificate),
+        },
+        credentials: adept::CredentialsRecord {
+            user: credentials.user.clone(),
+            username: credentials
+                .username
+                .as_ref()
+                .map(|x| adept::UsernameRecord {
+                    method: x.method.clone(),
+                    username: x.username.clone(),
+                }),
+            pkcs12: b64(&pkcs12),
+            license_certificate: b64(&credentials.license_certificate),
+            private_license_key: b64(&credentials.private_license_key),
+            authentication_certificate: b64(&record.services.auth_certificate),
+        },
+        activation_token: adept::ActivationTokenRecord {
+            device: record.activated_device.device.clone(),
+            fingerprint: record.activated_device.fingerprint.clone(),
+            device_type: record.activated_device.device_type.clone(),
+            activation_url: record.activated_device.activation_url.clone(),
+            user: record.activated_device.user.clone(),
+            signature: record.activated_device.signature.clone(),
+        },
+    })
+}
+
+fn serialize_device_xml(
+    device_info: &DeviceInfo,
+    identity: &DeviceIdentity,
+) -> crate::Result<String> {
+    let version = |name: &str, value: &str| adept::VersionRecord {
+        name: name.to_string(),
+        value: value.to_string(),
+    };
+
+    adept::serialize_device_record(adept::DeviceRecordData {
+        device_class: device_info.client_version.clone(),
+        device_serial: identity.device_serial.clone(),
+        device_name: identity.device_name.clone(),
+        device_type: device_info.device_type.clone(),
+        versions: vec![
+            version("hobbes", &device_info.software_version),
+            version("clientOS", &device_info.client_os),
+            version("clientLocale", &device_info.client_locale),
+        ],
+        fingerprint: device_info.fingerprint.clone(),
+    })
+}
+
+fn write_activation_record(dir: &Path, record: &ActivationRecord) -> crate::Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("could not create {}", dir.display()))?;
+
+    let activation_xml =
+        serialize_activation_xml(record).context("could not serialize activation.xml")?;
+    let device_xml = serialize_device_xml(record.device_info, record.identity)
+        .context("could not serialize device.xml")?;
+
+    std::fs::write(dir.join(ACTIVATION_FILE), activation_xml)
+        .context("could not write activation.xml")?;
+    std::fs::write(dir.join(DEVICE_FILE), device_xml).context("could not write device.xml")?;
+    std::fs::write(dir.join(DEVICE_SALT_FILE), record.identity.device_key)
+        .context("could not write devicesalt")?;
+
+    Ok(())
+}
+
+/// Writes the activation records of a secondary device into `mount_dir`, e.g. the root of a
+/// mounted e-reader, so the device can open resources fulfilled with the account.
+pub fn write_device_activation(
+    mount_dir: &Path,
+    account: &AdobeAccount,
+    device: &SecondaryDevice,
+    activation: &SecondaryDeviceActivation,
+) -> crate::Result<()> {
+    write_activation_record(
+        &mount_dir.join(ADE_DIR),
+        &ActivationRecord {
+            services: &account.services,
+            user_info_url: &activation.user_info_url,
+            activation_certificate: &activation.activation_certificate,
+            user_credentials: &account.user_credentials,
+            device_info: &device.device_info,
+            identity: &device.identity,
+            activated_device: &activation.activated_device,
+        },
+    )
+}
+
+/// Writes the activation records of one of the account's devices into `dir`. The device is
+/// picked by its id, the first device is written if `device` is `None`.
+///
+/// `device.xml` and `devicesalt` are written from the identity stored with the device, so they
+/// match its registered fingerprint. Devices without one can't be exported.
+pub fn write_account_activation(
+    dir: &Path,
+    account: &AdobeAccount,
+    device: Option<&str>,
+    user_info_url: &str,
+    activation_certificate: &[u8],
+) -> crate::Result<()> {
+    let device = account.device(device)?;
+    // Accounts migrated from before the token was stored don't have its signature.
+    if device.activation_token.signature.is_empty() {
+        return Err(Error::InvalidData(
+            "the account has no stored activation token, activate the device again to export it"
+                .to_string(),
+        ));
+    }
+
+    let identity = device.identity.as_ref().ok_or_else(|| {
+        Error::InvalidData(format!(
+            "device {} has no stored device key, its fingerprint wasn't derived from one",
+            device.id()
+        ))
+    })?;
+
+    write_activation_record(
+        dir,
+        &ActivationRecord {
+            services: &account.services,
+            user_info_url,
+            activation_certificate,
+            user_credentials: &account.user_credentials,
+            device_info: &device.device_info,
+            identity,
+            activated_device: &device.activation_token,
+        },
+    )
+}
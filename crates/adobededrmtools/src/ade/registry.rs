@@ -0,0 +1,386 @@
+use std::collections::BTreeMap;
+
+use adobededrmtools_crypto::{Pkey, decrypt_aes, unb64};
+
+use crate::AccountUsername;
+use crate::{Error, error::Context};
+
+// ADE on Windows keeps the activation in the registry, e.g.:
+// [HKEY_CURRENT_USER\Software\Adobe\Adept\Activation\0001]
+// @="credentials"
+//
+// [HKEY_CURRENT_USER\Software\Adobe\Adept\Activation\0001\0002]
+// @="privateLicenseKey"
+// "value"="..."
+//
+// [HKEY_CURRENT_USER\Software\Adobe\Adept\Device]
+// "key"=hex:01,00,00,00,d0,8c,...
+//
+// Every numbered subkey is an element of activation.xml, named by its default value.
+
+const ACTIVATION_KEY: &str = r"\software\adobe\adept\activation";
+const DEVICE_KEY: &str = r"\software\adobe\adept\device";
+
+/// ADE activation found in an exported `Software\Adobe\Adept` registry tree.
+#[derive(Debug, Clone)]
+pub struct RegistryActivation {
+    pub credentials: Vec<RegistryCredentials>,
+    pub activation_token: Option<RegistryActivationToken>,
+    /// Device key protected with Windows DPAPI. It can only be unprotected on the machine and by
+    /// the Windows user the export comes from.
+    pub protected_device_key: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RegistryCredentials {
+    pub user: String,
+    pub username: Option<AccountUsername>,
+    pub encrypted_private_license_key: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RegistryActivationToken {
+    pub device: String,
+    pub fingerprint: String,
+    pub device_type: String,
+}
+
+impl RegistryCredentials {
+    /// Decrypts the PKCS#8 private license key with the unprotected device key.
+    pub fn decrypt_private_license_key(&self, device_key: &[u8; 16]) -> crate::Result<Vec<u8>> {
+        // ADE encrypts the key with a zero IV, which is not prepended to the ciphertext.
+        let mut data = vec![0; 16];
+        data.extend_from_slice(&self.encrypted_private_license_key);
+
+        let private_license_key =
+            decrypt_aes(device_key, &data).context("could not decrypt private license key")?;
+        Pkey::from_der(&private_license_key)
+            .context("decrypted private license key is invalid, the device key may be wrong")?;
+        Ok(private_license_key)
+    }
+}
+
+/// Parses a `.reg` file exported by regedit, in either UTF-16 or UTF-8.
+pub fn parse_registry_activation(reg: &[u8]) -> crate::Result<RegistryActivation> {
+    let text = decode_reg_text(reg)?;
+    let reg_file = parse_reg_file(&text).context("could not parse reg file")?;
+
+    let activation_key = reg_file
+        .find_key(ACTIVATION_KEY)
+        .context("no ADE activation key in the reg file")?;
+
+    let mut credentials = Vec::new();
+    let mut activation_token = None;
+
+    for (path, values) in reg_file.subkeys(activation_key) {
+        match default_value(values) {
+            Some("credentials") => credentials.push(parse_credentials(&reg_file, path)?),
+            Some("activationToken") => {
+                activation_token = Some(parse_activation_token(&reg_file, path)?)
+            }
+            _ => {}
+        }
+    }
+
+    let protected_device_key = reg_file
+        .find_key(DEVICE_KEY)
+        .and_then(|x| reg_file.keys[x].get("key"))
+        .and_then(|x| match x {
+            RegValue::Binary(v) => Some(v.clone()),
+            _ => None,
+        });
+
+    Ok(RegistryActivation {
+        credentials,
+        activation_token,
+        protected_device_key,
+    })
+}
+
+fn parse_credentials(reg_file: &RegFile, path: &str) -> crate::Result<RegistryCredentials> {
+    let mut user = None;
+    let mut username = None;
+    let mut encrypted_private_license_key = None;
+
+    for (_, values) in reg_file.subkeys(path) {
+        match default_value(values) {
+            Some("user") => user = string_value(values, "value"),
+            Some("username") => {
+                username = Some(AccountUsername {
+                    method: string_value(values, "method").context("username has no method")?,
+                    username: string_value(values, "value").context("username has no value")?,
+                })
+            }
+            Some("privateLicenseKey") => {
+                let value =
+                    string_value(values, "value").context("privateLicenseKey has no value")?;
+                encrypted_private_license_key = Some(unb64(&value)?);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(RegistryCredentials {
+        user: user.context("credentials have no user")?,
+        username,
+        encrypted_private_license_key: encrypted_private_license_key
+            .context("credentials have no privateLicenseKey")?,
+    })
+}
+
+fn parse_activation_token(
+    reg_file: &RegFile,
+    path: &str,
+) -> crate::Result<RegistryActivationToken> {
+    let field = |name: &str| {
+        reg_file
+            .subkeys(path)
+            .find(|(_, values)| default_value(values) == Some(name))
+            .and_then(|(_, values)| string_value(values, "value"))
+            .with_context(|| format!("activation token has no {}", name))
+    };
+
+    Ok(RegistryActivationToken {
+        device: field("device")?,
+        fingerprint: field("fingerprint")?,
+        device_type: field("deviceType")?,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RegValue {
+    String(String),
+    Dword(u32),
+    Binary(Vec<u8>),
+}
+
+type RegValues = BTreeMap<String, RegValue>;
+
+struct RegFile {
+    /// Values by lowercase key path. The default value has an empty name.
+    keys: BTreeMap<String, RegValues>,
+}
+
+impl RegFile {
+    fn find_key(&self, suffix: &str) -> Option<&str> {
+        self.keys
+            .keys()
+            .find(|x| x.ends_with(suffix))
+            .map(|x| x.as_str())
+    }
+
+    /// Direct subkeys of `path`, ordered by name.
+    fn subkeys<'a>(&'a self, path: &'a str) -> impl Iterator<Item = (&'a str, &'a RegValues)> {
+        self.keys.iter().filter_map(move |(key, values)| {
+            let name = key.strip_prefix(path)?.strip_prefix('\\')?;
+            (!name.contains('\\')).then_some((key.as_str(), values))
+        })
+    }
+}
+
+fn default_value(values: &RegValues) -> Option<&str> {
+    match values.get("") {
+        Some(RegValue::String(v)) => Some(v),
+        _ => None,
+    }
+}
+
+fn string_value(values: &RegValues, name: &str) -> Option<String> {
+    match values.get(name) {
+        Some(RegValue::String(v)) => Some(v.clone()),
+        _ => None,
+    }
+}
+
+fn decode_reg_text(reg: &[u8]) -> crate::Result<String> {
+    // regedit exports UTF-16LE with a BOM.
+    if let Some(utf16) = reg.strip_prefix(&[0xff, 0xfe]) {
+        let units = utf16
+            .chunks_exact(2)
+            .map(|x| u16::from_le_bytes([x[0], x[1]]))
+            .collect::<Vec<_>>();
+        return String::from_utf16(&units)
+            .ok()
+            .context("reg file is not valid UTF-16");
+    }
+
+    let utf8 = reg.strip_prefix(&[0xef, 0xbb, 0xbf]).unwrap_or(reg);
+    String::from_utf8(utf8.to_vec())
+        .ok()
+        .context("reg file is neither UTF-16 nor UTF-8")
+}
+
+fn parse_reg_file(text: &str) -> crate::Result<RegFile> {
+    let mut keys = BTreeMap::new();
+    let mut current_key: Option<String> = None;
+
+    for line in logical_lines(text) {
+        let line = line.trim();
+        if line.is_empty()
+            || line.starts_with(';')
+            || line.starts_with("Windows Registry Editor")
+            || line == "REGEDIT4"
+        {
+            continue;
+        }
+
+        if let Some(path) = line.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+            // Deleted keys don't exist in the dump.
+            current_key = (!path.starts_with('-')).then(|| path.to_lowercase());
+            if let Some(key) = &current_key {
+                keys.entry(key.clone()).or_insert_with(RegValues::new);
+            }
+            continue;
+        }
+
+        let Some(key) = &current_key else {
+            continue;
+        };
+
+        let (name, value) =
+            parse_value_line(line).with_context(|| format!("invalid value line: {}", line))?;
+        if let Some(value) = value {
+            keys.get_mut(key)
+                .expect("current key is inserted")
+                .insert(name, value);
+        }
+    }
+
+    Ok(RegFile { keys })
+}
+
+/// Joins lines of binary values continued with a trailing backslash.
+fn logical_lines(text: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for line in text.lines() {
+        let line = if current.is_empty() {
+            line
+        } else {
+            line.trim_start()
+        };
+
+        match line.trim_end().strip_suffix('\\') {
+            Some(continued) if current.contains("=hex") || continued.contains("=hex") => {
+                current.push_str(continued);
+            }
+            _ => {
+                current.push_str(line);
+                lines.push(std::mem::take(&mut current));
+            }
+        }
+    }
+
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+fn parse_value_line(line: &str) -> crate::Result<(String, Option<RegValue>)> {
+    let (name, rest) = match line.strip_prefix('@') {
+        Some(rest) => (String::new(), rest),
+        None => parse_quoted(line)?,
+    };
+
+    let data = rest
+        .trim_start()
+        .strip_prefix('=')
+        .context("no = after value name")?
+        .trim();
+
+    let value = if data == "-" {
+        None
+    } else if data.starts_with('"') {
+        Some(RegValue::String(parse_quoted(data)?.0))
+    } else if let Some(dword) = data.strip_prefix("dword:") {
+        Some(RegValue::Dword(
+            u32::from_str_radix(dword, 16)
+                .ok()
+                .context("invalid dword")?,
+        ))
+    } else if let Some(hex) = data.strip_prefix("hex") {
+        // Either hex:.. or hex(type):..
+        let (_, bytes) = hex.split_once(':').context("invalid hex value")?;
+        Some(RegValue::Binary(
+            bytes
+                .split(',')
+                .map(|x| x.trim())
+                .filter(|x| !x.is_empty())
+                .map(|x| u8::from_str_radix(x, 16))
+                .collect::<Result<_, _>>()
+                .ok()
+                .context("invalid hex byte")?,
+        ))
+    } else {
+        return Err(Error::Unsupported("registry value type".to_string()));
+    };
+
+    Ok((name, value))
+}
+
+/// Parses a quoted string with `\\` and `\"` escapes, returning the rest of the input.
+fn parse_quoted(s: &str) -> crate::Result<(String, &str)> {
+    let s = s.strip_prefix('"').context("expected quoted string")?;
+
+    let mut out = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                let (_, escaped) = chars.next().context("unterminated escape")?;
+                out.push(escaped);
+            }
+            '"' => return Ok((out, &s[i + 1..])),
+            c => out.push(c),
+        }
+    }
+
+    Err(Error::InvalidData("unterminated quoted string".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_registry_activation;
+
+    const DEVICE_KEY: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+    #[test]
+    fn test_parse_registry_activation() {
+        let reg = include_bytes!("../../testdata/adept_activation.reg");
+        let activation = parse_registry_activation(reg).expect("parse_registry_activation failed");
+
+        assert_eq!(activation.credentials.len(), 1);
+        let credentials = &activation.credentials[0];
+        assert_eq!(
+            credentials.user,
+            "urn:uuid:4b4e7bd5-4b2b-4bb5-a2a4-3a0e5f0cbd2b"
+        );
+        let username = credentials.username.as_ref().expect("no username");
+        assert_eq!(username.method, "AdobeID");
+        assert_eq!(username.username, "reader@example.com");
+
+        let token = activation.activation_token.expect("no activation token");
+        assert_eq!(
+            token.device,
+            "urn:uuid:00000000-0000-0000-0000-000000000001"
+        );
+        assert_eq!(token.fingerprint, "lKE4VIqErngzCsut4k2CLpGp4Ag=");
+        assert_eq!(token.device_type, "standalone");
+
+        let protected_device_key = activation.protected_device_key.expect("no device key");
+        assert_eq!(protected_device_key.len(), 60);
+        assert_eq!(protected_device_key[..4], [1, 0, 0, 0]);
+
+        let private_license_key = credentials
+            .decrypt_private_license_key(&DEVICE_KEY)
+            .expect("decrypt_private_license_key failed");
+        assert_eq!(
+            private_license_key,
+            include_bytes!("../../testdata/private_license_key.der")
+        );
+
+        assert!(credentials.decrypt_private_license_key(&[0; 16]).is_err());
+    }
+}
@@ -1,13 +1,92 @@
-use anyhow::Context;
-use serde::Deserialize;
+use std::time::Duration;
 
-use super::xml::deserialize_xml;
+use chrono::{DateTime, Utc};
+use xmltree::Element;
 
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename = "fulfillmentToken")]
+use super::metadata::{ResourceMetadata, parse_metadata};
+use super::permissions::{Permissions, parse_permissions};
+use super::xml::{child, child_text, element_text, parse_datetime};
+use crate::{Clock, Error, error::Context};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FulfillmentType {
+    Buy,
+    Loan,
+    Other(String),
+}
+
+impl FulfillmentType {
+    fn from_name(name: &str) -> Self {
+        match name {
+            "buy" => FulfillmentType::Buy,
+            "loan" => FulfillmentType::Loan,
+            _ => FulfillmentType::Other(name.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 struct FulfillmentToken {
-    #[serde(rename = "operatorURL")]
-    pub operator_url: String,
+    operator_url: String,
+    fulfillment_type: Option<FulfillmentType>,
+    auth: Option<String>,
+    distributor: Option<String>,
+    transaction: Option<String>,
+    purchase: Option<DateTime<Utc>>,
+    expiration: Option<DateTime<Utc>>,
+    resource: Option<String>,
+    metadata: ResourceMetadata,
+    permissions: Permissions,
+}
+
+fn parse_fulfillment_token(s: &str) -> crate::Result<FulfillmentToken> {
+    let root = Element::parse(s.as_bytes())?;
+    if root.name != "fulfillmentToken" {
+        return Err(Error::InvalidData(format!(
+            "expected fulfillmentToken, got {}",
+            root.name
+        )));
+    }
+
+    let text = |element: &Element, name: &str| element.get_child(name).map(element_text);
+    // The token can still be fulfilled without the optional fields, so invalid ones are dropped.
+    let datetime = |name: &str| {
+        let value = text(&root, name)?;
+        parse_datetime(&value)
+            .inspect_err(|err| log::warn!("Ignoring invalid {} in the ACSM: {}", name, err))
+            .ok()
+    };
+
+    let resource_item_info = root.get_child("resourceItemInfo");
+    let metadata = resource_item_info
+        .and_then(|x| x.get_child("metadata"))
+        .map(parse_metadata)
+        .unwrap_or_default();
+
+    let permissions = resource_item_info
+        .and_then(|x| x.get_child("licenseToken"))
+        .and_then(|x| x.get_child("permissions"))
+        .map(parse_permissions)
+        .unwrap_or_default();
+
+    Ok(FulfillmentToken {
+        operator_url: child_text(&root, "operatorURL")?,
+        fulfillment_type: root
+            .attributes
+            .get("fulfillmentType")
+            .map(|x| FulfillmentType::from_name(x)),
+        auth: root.attributes.get("auth").cloned(),
+        distributor: text(&root, "distributor"),
+        transaction: text(&root, "transaction"),
+        purchase: datetime("purchase"),
+        expiration: datetime("expiration"),
+        resource: resource_item_info
+            .map(|x| child(x, "resource"))
+            .transpose()?
+            .map(element_text),
+        metadata,
+        permissions,
+    })
 }
 
 pub struct Acsm {
@@ -16,16 +95,17 @@ pub struct Acsm {
 }
 
 impl Acsm {
-    pub fn from_string(s: String) -> anyhow::Result<Self> {
-        let parsed = deserialize_xml(&s).context("failed to deserialize xml")?;
+    pub fn from_string(s: String) -> crate::Result<Self> {
+        let parsed = parse_fulfillment_token(&s).context("failed to parse fulfillment token")?;
         Ok(Self { raw: s, parsed })
     }
 
-    pub fn from_str(s: &str) -> anyhow::Result<Self> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> crate::Result<Self> {
         Self::from_string(s.to_string())
     }
 
-    pub fn from_file(filepath: &str) -> anyhow::Result<Self> {
+    pub fn from_file(filepath: &str) -> crate::Result<Self> {
         Self::from_string(
             std::fs::read_to_string(filepath).context("could not read acsm from file")?,
         )
@@ -38,4 +118,220 @@ impl Acsm {
     pub fn operator_url(&self) -> &str {
         &self.parsed.operator_url
     }
+
+    pub fn fulfillment_type(&self) -> Option<&FulfillmentType> {
+        self.parsed.fulfillment_type.as_ref()
+    }
+
+    /// Authority the token is issued for, e.g. `user`.
+    pub fn auth(&self) -> Option<&str> {
+        self.parsed.auth.as_deref()
+    }
+
+    pub fn distributor(&self) -> Option<&str> {
+        self.parsed.distributor.as_deref()
+    }
+
+    pub fn transaction(&self) -> Option<&str> {
+        self.parsed.transaction.as_deref()
+    }
+
+    pub fn purchase(&self) -> Option<DateTime<Utc>> {
+        self.parsed.purchase
+    }
+
+    /// Time after which the operator refuses to fulfill the token.
+    pub fn expiration(&self) -> Option<DateTime<Utc>> {
+        self.parsed.expiration
+    }
+
+    /// Fails with [`Error::AcsmExpired`] if the token has expired.
+    pub fn check_expiration(&self, clock: &dyn Clock) -> crate::Result<()> {
+        match self.parsed.expiration {
+            Some(expiration) if expiration <= clock.now() => Err(Error::AcsmExpired { expiration }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the token expires in less than `duration`, or has already expired.
+    pub fn expires_within(&self, clock: &dyn Clock, duration: Duration) -> bool {
+        self.parsed.expiration.is_some_and(|expiration| {
+            (expiration - clock.now())
+                .to_std()
+                .map_or(true, |remaining| remaining < duration)
+        })
+    }
+
+    /// Resource id, `urn:uuid:...`.
+    pub fn resource(&self) -> Option<&str> {
+        self.parsed.resource.as_deref()
+    }
+
+    pub fn metadata(&self) -> &ResourceMetadata {
+        &self.parsed.metadata
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.parsed.metadata.title.as_deref()
+    }
+
+    pub fn creator(&self) -> Option<&str> {
+        self.parsed.metadata.creator.as_deref()
+    }
+
+    pub fn publisher(&self) -> Option<&str> {
+        self.parsed.metadata.publisher.as_deref()
+    }
+
+    pub fn language(&self) -> Option<&str> {
+        self.parsed.metadata.language.as_deref()
+    }
+
+    pub fn format(&self) -> Option<&str> {
+        self.parsed.metadata.format.as_deref()
+    }
+
+    pub fn identifier(&self) -> Option<&str> {
+        self.parsed.metadata.identifier.as_deref()
+    }
+
+    pub fn permissions(&self) -> &Permissions {
+        &self.parsed.permissions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{DateTime, Utc};
+
+    use super::{Acsm, FulfillmentType};
+    use crate::{Clock, Error};
+
+    struct FixedClock(&'static str);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> DateTime<Utc> {
+            DateTime::parse_from_rfc3339(self.0).unwrap().into()
+        }
+    }
+
+    const ACSM: &str = r#"<fulfillmentToken fulfillmentType="loan" auth="user" xmlns="http://ns.adobe.com/adept">
+  <distributor>urn:uuid:00000000-0000-0000-0000-00000000000d</distributor>
+  <operatorURL>https://acs.example.com/fulfillment</operatorURL>
+  <transaction>ACS4-1234</transaction>
+  <purchase>2025-03-01T10:00:00-08:00</purchase>
+  <expiration>2025-03-01T18:10:00+00:00</expiration>
+  <resourceItemInfo>
+    <resource>urn:uuid:00000000-0000-0000-0000-00000000000a</resource>
+    <resourceItem>0</resourceItem>
+    <metadata>
+      <dc:title xmlns:dc="http://purl.org/dc/elements/1.1/">Example Book</dc:title>
+      <dc:creator xmlns:dc="http://purl.org/dc/elements/1.1/">Jane Doe</dc:creator>
+      <dc:publisher xmlns:dc="http://purl.org/dc/elements/1.1/">Example Press</dc:publisher>
+      <dc:identifier xmlns:dc="http://purl.org/dc/elements/1.1/">urn:isbn:9780000000000</dc:identifier>
+      <dc:format xmlns:dc="http://purl.org/dc/elements/1.1/">application/epub+zip</dc:format>
+      <dc:language xmlns:dc="http://purl.org/dc/elements/1.1/">en</dc:language>
+    </metadata>
+    <licenseToken>
+      <resource>urn:uuid:00000000-0000-0000-0000-00000000000a</resource>
+      <permissions>
+        <display>
+          <until>2025-03-15T18:00:00Z</until>
+        </display>
+        <excerpt/>
+        <print>
+          <count initial="10" max="20" incrementInterval="86400"/>
+        </print>
+      </permissions>
+    </licenseToken>
+  </resourceItemInfo>
+  <hmac>aG1hYw==</hmac>
+</fulfillmentToken>"#;
+
+    #[test]
+    fn test_parse_acsm() {
+        let acsm = Acsm::from_str(ACSM).expect("Acsm::from_str failed");
+
+        assert_eq!(acsm.operator_url(), "https://acs.example.com/fulfillment");
+        assert_eq!(acsm.fulfillment_type(), Some(&FulfillmentType::Loan));
+        assert_eq!(acsm.auth(), Some("user"));
+        assert_eq!(acsm.transaction(), Some("ACS4-1234"));
+        assert_eq!(
+            acsm.purchase().map(|x| x.to_rfc3339()),
+            Some("2025-03-01T18:00:00+00:00".to_string())
+        );
+        assert_eq!(
+            acsm.resource(),
+            Some("urn:uuid:00000000-0000-0000-0000-00000000000a")
+        );
+        assert_eq!(acsm.title(), Some("Example Book"));
+        assert_eq!(acsm.creator(), Some("Jane Doe"));
+        assert_eq!(acsm.publisher(), Some("Example Press"));
+        assert_eq!(acsm.language(), Some("en"));
+        assert_eq!(acsm.format(), Some("application/epub+zip"));
+
+        let permissions = acsm.permissions();
+        assert_eq!(
+            permissions.display[0].until.map(|x| x.to_rfc3339()),
+            Some("2025-03-15T18:00:00+00:00".to_string())
+        );
+        assert_eq!(permissions.excerpt.len(), 1);
+        let count = permissions.print[0].count.as_ref();
+        assert_eq!(count.map(|x| (x.initial, x.max)), Some((10, Some(20))));
+        assert!(permissions.play.is_empty());
+    }
+
+    #[test]
+    fn test_acsm_expiration() {
+        let acsm = Acsm::from_str(ACSM).expect("Acsm::from_str failed");
+        let hour = Duration::from_secs(60 * 60);
+
+        let valid = FixedClock("2025-03-01T16:00:00Z");
+        assert!(acsm.check_expiration(&valid).is_ok());
+        assert!(!acsm.expires_within(&valid, hour));
+
+        let expiring = FixedClock("2025-03-01T17:30:00Z");
+        assert!(acsm.check_expiration(&expiring).is_ok());
+        assert!(acsm.expires_within(&expiring, hour));
+
+        let expired = FixedClock("2025-03-01T18:10:00Z");
+        assert!(acsm.expires_within(&expired, hour));
+        match acsm.check_expiration(&expired) {
+            Err(Error::AcsmExpired { expiration }) => {
+                assert_eq!(expiration.to_rfc3339(), "2025-03-01T18:10:00+00:00")
+            }
+            other => panic!("expected AcsmExpired, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_acsm_with_invalid_optional_fields() {
+        let acsm = ACSM
+            .replace(
+                "<expiration>2025-03-01T18:10:00+00:00</expiration>",
+                "<expiration>next tuesday</expiration>",
+            )
+            .replace(
+                "<until>2025-03-15T18:00:00Z</until>",
+                "<until>2025-03-15</until>",
+            )
+            .replace(r#"initial="10""#, r#"initial="ten""#);
+        let acsm = Acsm::from_str(&acsm).expect("invalid optional fields must not fail parsing");
+
+        assert_eq!(acsm.expiration(), None);
+        assert!(
+            acsm.check_expiration(&FixedClock("2030-01-01T00:00:00Z"))
+                .is_ok()
+        );
+        assert!(acsm.purchase().is_some());
+        assert_eq!(acsm.title(), Some("Example Book"));
+
+        let permissions = acsm.permissions();
+        assert_eq!(permissions.display.len(), 1);
+        assert_eq!(permissions.display[0].until, None);
+        assert_eq!(permissions.print.len(), 1);
+        assert_eq!(permissions.print[0].count, None);
+    }
 }
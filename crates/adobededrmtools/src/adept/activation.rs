@@ -22,7 +22,7 @@ pub struct ActivationServiceInfo {
 pub async fn get_activation_service_info<H: HttpClient>(
     http_client: &H,
     activation_url: &str,
-) -> anyhow::Result<ActivationServiceInfo> {
+) -> crate::Result<ActivationServiceInfo> {
     let response = parse_response(
         http_client
             .request(make_get(activation_url, "/ActivationServiceInfo"))
@@ -63,7 +63,7 @@ pub struct SignInMethod {
 pub async fn get_authentication_service_info<H: HttpClient>(
     http_client: &H,
     authentication_url: &str,
-) -> anyhow::Result<AuthenticationServiceInfo> {
+) -> crate::Result<AuthenticationServiceInfo> {
     let response = parse_response(
         http_client
             .request(make_get(authentication_url, "/AuthenticationServiceInfo"))
@@ -125,7 +125,7 @@ pub async fn sign_in<H: HttpClient>(
     authentication_url: &str,
     method: &str,
     data: SignInData,
-) -> anyhow::Result<Credentials> {
+) -> crate::Result<Credentials> {
     let req = AdeptSignIn {
         adept_xmlns: ADEPT_XMLNS,
         method,
@@ -146,6 +146,63 @@ pub async fn sign_in<H: HttpClient>(
     Ok(response)
 }
 
+// Sent by ADE to `AddSignInDirect` when an anonymous user is authorized with an Adobe ID. No
+// capture of that request is at hand: the layout is the `adept:signIn` of `SignInDirect` built
+// by libgourou's `DRMProcessor::signIn`, without the new keys since the user keeps its own, plus
+// the `adept:user` and `adept:signature` that libgourou adds to the requests of an existing user
+// (see `DRMProcessor::activateDevice`).
+// https://forge.soutade.fr/soutade/libgourou/src/commit/d3c90f03bba187292c747080592840123f94f285/src/libgourou.cpp
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "adept:signIn")]
+pub struct AdeptAddSignIn {
+    #[serde(rename = "@xmlns:adept")]
+    pub adept_xmlns: &'static str,
+    #[serde(rename = "@method")]
+    pub method: String,
+
+    #[serde(rename = "adept:signInData")]
+    pub sign_in_data: String,
+    #[serde(rename = "adept:user")]
+    pub user: String,
+    #[serde(rename = "adept:signature")]
+    pub signature: Option<String>,
+}
+
+impl_set_signature!(AdeptAddSignIn, signature);
+
+pub struct AddSignInData {
+    pub method: String,
+    pub sign_in_data: String,
+    pub user: String,
+}
+
+/// Adds a sign in method to an existing user, e.g. links an anonymous user to an Adobe ID.
+pub async fn add_sign_in<H: HttpClient>(
+    http_client: &H,
+    signer: &Signer,
+    authentication_url: &str,
+    data: AddSignInData,
+) -> crate::Result<()> {
+    let req = compute_signature(
+        signer,
+        AdeptAddSignIn {
+            adept_xmlns: ADEPT_XMLNS,
+            method: data.method,
+            sign_in_data: data.sign_in_data,
+            user: data.user,
+            signature: None,
+        },
+    )?;
+
+    parse_response::<()>(
+        http_client
+            .request(make_post(authentication_url, "/AddSignInDirect", &req)?)
+            .await?,
+    )?;
+
+    Ok(())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename = "adept:activate")]
 pub struct Activate {
@@ -206,13 +263,20 @@ pub struct ActivationToken {
     pub signature: String,
 }
 
-pub struct ActivateData {
+pub struct ActivateDeviceData {
     pub software_version: String,
     pub client_os: String,
     pub client_locale: String,
     pub client_version: String,
     pub device_type: String,
     pub fingerprint: String,
+}
+
+pub struct ActivateData {
+    /// Device sending the activation request.
+    pub device: ActivateDeviceData,
+    /// Device being activated. Same as `device` unless activating a secondary device.
+    pub target_device: ActivateDeviceData,
     pub nonce: String,
     pub expiration: String,
     pub user: String,
@@ -223,24 +287,24 @@ pub async fn activate<H: HttpClient>(
     signer: &Signer,
     activation_url: &str,
     data: ActivateData,
-) -> anyhow::Result<ActivationToken> {
+) -> crate::Result<ActivationToken> {
     let req = compute_signature(
         signer,
         Activate {
             adept_xmlns: ADEPT_XMLNS,
             request_type: "initial".to_string(),
-            fingerprint: data.fingerprint.clone(),
-            device_type: data.device_type.clone(),
-            client_os: data.client_os.clone(),
-            client_locale: data.client_locale.clone(),
-            client_version: data.client_version.clone(),
+            fingerprint: data.device.fingerprint,
+            device_type: data.device.device_type,
+            client_os: data.device.client_os,
+            client_locale: data.device.client_locale,
+            client_version: data.device.client_version,
             target_device: TargetDevice {
-                software_version: data.software_version,
-                client_os: data.client_os,
-                client_locale: data.client_locale,
-                client_version: data.client_version,
-                device_type: data.device_type,
-                fingerprint: data.fingerprint,
+                software_version: data.target_device.software_version,
+                client_os: data.target_device.client_os,
+                client_locale: data.target_device.client_locale,
+                client_version: data.target_device.client_version,
+                device_type: data.target_device.device_type,
+                fingerprint: data.target_device.fingerprint,
             },
             nonce: data.nonce,
             expiration: data.expiration,
@@ -258,6 +322,67 @@ pub async fn activate<H: HttpClient>(
     Ok(response)
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "adept:deactivate")]
+pub struct Deactivate {
+    #[serde(rename = "@xmlns:adept")]
+    pub adept_xmlns: &'static str,
+    #[serde(rename = "adept:user")]
+    pub user: String,
+    #[serde(rename = "adept:device")]
+    pub device: String,
+    #[serde(rename = "adept:fingerprint")]
+    pub fingerprint: String,
+    #[serde(rename = "adept:deviceType")]
+    pub device_type: String,
+    #[serde(rename = "adept:nonce")]
+    pub nonce: String,
+    #[serde(rename = "adept:expiration")]
+    pub expiration: String,
+    #[serde(rename = "adept:signature")]
+    pub signature: Option<String>,
+}
+
+impl_set_signature!(Deactivate, signature);
+
+pub struct DeactivateData {
+    pub user: String,
+    pub device: String,
+    pub fingerprint: String,
+    pub device_type: String,
+    pub nonce: String,
+    pub expiration: String,
+}
+
+pub async fn deactivate<H: HttpClient>(
+    http_client: &H,
+    signer: &Signer,
+    activation_url: &str,
+    data: DeactivateData,
+) -> crate::Result<()> {
+    let req = compute_signature(
+        signer,
+        Deactivate {
+            adept_xmlns: ADEPT_XMLNS,
+            user: data.user,
+            device: data.device,
+            fingerprint: data.fingerprint,
+            device_type: data.device_type,
+            nonce: data.nonce,
+            expiration: data.expiration,
+            signature: None,
+        },
+    )?;
+
+    parse_response::<()>(
+        http_client
+            .request(make_post(activation_url, "/Deactivate", &req)?)
+            .await?,
+    )?;
+
+    Ok(())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename = "adept:licenseServiceRequest")]
 pub struct LicenseServiceRequest {
@@ -291,7 +416,7 @@ pub async fn init_license_service<H: HttpClient>(
     signer: &Signer,
     activation_url: &str,
     data: InitLicenseService,
-) -> anyhow::Result<()> {
+) -> crate::Result<()> {
     let req = compute_signature(
         signer,
         LicenseServiceRequest {
@@ -305,11 +430,128 @@ pub async fn init_license_service<H: HttpClient>(
         },
     )?;
 
-    let response = parse_response(
+    parse_response::<()>(
         http_client
             .request(make_post(activation_url, "/InitLicenseService", &req)?)
             .await?,
     )?;
 
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "adept:userInfoRequest")]
+pub struct UserInfoRequest {
+    #[serde(rename = "@xmlns:adept")]
+    pub adept_xmlns: &'static str,
+    #[serde(rename = "adept:user")]
+    pub user: String,
+    #[serde(rename = "adept:nonce")]
+    pub nonce: String,
+    #[serde(rename = "adept:expiration")]
+    pub expiration: String,
+    #[serde(rename = "adept:signature")]
+    pub signature: Option<String>,
+}
+
+impl_set_signature!(UserInfoRequest, signature);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "userInfo")]
+pub struct UserInfo {
+    pub user: String,
+    #[serde(default)]
+    pub username: Option<UserInfoUsername>,
+    #[serde(rename = "activationCount", default)]
+    pub activation_count: Option<u32>,
+    #[serde(rename = "maxActivationCount", default)]
+    pub max_activation_count: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserInfoUsername {
+    #[serde(rename = "@method")]
+    pub method: String,
+    #[serde(rename = "$text")]
+    pub username: String,
+}
+
+pub struct UserInfoData {
+    pub user: String,
+    pub nonce: String,
+    pub expiration: String,
+}
+
+pub async fn get_user_info<H: HttpClient>(
+    http_client: &H,
+    signer: &Signer,
+    user_info_url: &str,
+    data: UserInfoData,
+) -> crate::Result<UserInfo> {
+    let req = compute_signature(
+        signer,
+        UserInfoRequest {
+            adept_xmlns: ADEPT_XMLNS,
+            user: data.user,
+            nonce: data.nonce,
+            expiration: data.expiration,
+            signature: None,
+        },
+    )?;
+
+    let response = parse_response(
+        http_client
+            .request(make_post(user_info_url, "/UserInfo", &req)?)
+            .await?,
+    )?;
+
     Ok(response)
 }
+
+#[cfg(test)]
+mod tests {
+    use adobededrmtools_crypto::make_signer;
+
+    use super::{ADEPT_XMLNS, AddSignInData, AdeptAddSignIn, add_sign_in};
+    use crate::{
+        adept::xml::serialize_xml,
+        http_client::testing::MockHttpClient,
+        testing::{AUTH_URL, USER, USER_KEY},
+    };
+
+    const ADD_SIGN_IN: &str = r#"<adept:signIn xmlns:adept="http://ns.adobe.com/adept" method="AdobeID"><adept:signInData>c2lnbkluRGF0YQ==</adept:signInData><adept:user>urn:uuid:4b4e7bd5-4b2b-4bb5-a2a4-3a0e5f0cbd2b</adept:user><adept:signature>c2lnbmF0dXJl</adept:signature></adept:signIn>"#;
+
+    #[tokio::test]
+    async fn test_add_sign_in_request() {
+        crate::init_test_rand();
+        let request = AdeptAddSignIn {
+            adept_xmlns: ADEPT_XMLNS,
+            method: "AdobeID".to_string(),
+            sign_in_data: "c2lnbkluRGF0YQ==".to_string(),
+            user: USER.to_string(),
+            signature: Some("c2lnbmF0dXJl".to_string()),
+        };
+        assert_eq!(serialize_xml(&request).unwrap(), ADD_SIGN_IN);
+
+        // The request sent only differs by its signature.
+        let http = MockHttpClient::new();
+        http.respond(&format!("{AUTH_URL}/AddSignInDirect"), "<success/>");
+        add_sign_in(
+            &http,
+            &make_signer(USER_KEY).unwrap(),
+            AUTH_URL,
+            AddSignInData {
+                method: "AdobeID".to_string(),
+                sign_in_data: "c2lnbkluRGF0YQ==".to_string(),
+                user: USER.to_string(),
+            },
+        )
+        .await
+        .expect("add_sign_in failed");
+
+        let body = http.requests().pop().unwrap().body;
+        let unsigned = &ADD_SIGN_IN[..ADD_SIGN_IN.find("<adept:signature>").unwrap()];
+        assert!(body.starts_with(unsigned));
+        assert!(body.ends_with("</adept:signature></adept:signIn>"));
+    }
+}
@@ -1,11 +1,17 @@
 use adobededrmtools_crypto::Signer;
 use serde::{Deserialize, Serialize};
+use xmltree::Element;
 
 use super::ADEPT_XMLNS;
-use super::request::{make_post, make_post_serialized};
-use super::response::parse_response;
-use super::signature::{SetSignature, compute_signature_raw, impl_set_signature};
-use super::{HttpClient, xml::serialize_xml};
+use super::http_client::HttpResponse;
+use super::request::{make_get, make_post, make_post_serialized};
+use super::response::{parse_response, parse_response_raw};
+use super::signature::{
+    SetSignature, compute_signature, compute_signature_raw, impl_set_signature,
+};
+use super::xml::{find_elements, serialize_xml, substitute_placeholder};
+use super::{HttpClient, Notification, make_notifications};
+use crate::error::Context;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename = "adept:credentials")]
@@ -33,7 +39,7 @@ pub async fn fulfillment_auth<H: HttpClient>(
     http_client: &H,
     operator_url: &str,
     data: FulfillmentAuthData,
-) -> anyhow::Result<()> {
+) -> crate::Result<()> {
     let req = FulfillmentCredentials {
         adept_xmlns: ADEPT_XMLNS,
         user: data.user,
@@ -42,12 +48,33 @@ pub async fn fulfillment_auth<H: HttpClient>(
         authentication_certificate: data.authentication_certificate,
     };
 
-    let response = parse_response(
+    parse_response::<()>(
         http_client
             .request(make_post(operator_url, "/Auth", &req)?)
             .await?,
     )?;
 
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "licenseServiceInfo")]
+pub struct LicenseServiceInfo {
+    #[serde(rename = "licenseURL")]
+    pub license_url: String,
+    pub certificate: String,
+}
+
+pub async fn get_license_service_info<H: HttpClient>(
+    http_client: &H,
+    license_url: &str,
+) -> crate::Result<LicenseServiceInfo> {
+    let response = parse_response(
+        http_client
+            .request(make_get(license_url, "/LicenseServiceInfo"))
+            .await?,
+    )?;
+
     Ok(response)
 }
 
@@ -111,6 +138,7 @@ pub struct FulfillmentResult {
     pub fulfillment: String,
     pub returnable: bool,
     pub initial: bool,
+    #[serde(default)]
     pub notify: Vec<FulfillmentNotify>,
     #[serde(rename = "resourceItemInfo")]
     pub resources: Vec<ResourceItemInfo>,
@@ -122,7 +150,6 @@ pub struct ResourceItemInfo {
     pub resource: String,
     #[serde(rename = "resourceItem")]
     pub resource_item: u32,
-    pub metadata: (),
     pub src: String,
     #[serde(rename = "downloadType")]
     pub download_type: String,
@@ -150,7 +177,6 @@ pub struct LicenseToken {
     #[serde(rename = "encryptedKey")]
     pub encrypted_key: EncryptedKey,
     pub model: String,
-    pub permissions: (),
     pub signature: String,
 }
 
@@ -184,12 +210,21 @@ pub struct FulfillmentData {
     pub fulfillment_token: String,
 }
 
+pub struct FulfillResponse {
+    pub envelope: Envelope,
+    pub notifications: Vec<Notification>,
+    /// Raw `resourceItemInfo` elements, as found in the response. They are matched to the
+    /// resources by their `resource` and `resourceItem`. License token signatures are checked
+    /// over the elements as received.
+    pub resource_items: Vec<Element>,
+}
+
 pub async fn fulfill<H: HttpClient>(
     http_client: &H,
     signer: &Signer,
     operator_url: &str,
     data: FulfillmentData,
-) -> anyhow::Result<Envelope> {
+) -> crate::Result<FulfillResponse> {
     let mut raw_req = Fulfill {
         adept_xmlns: ADEPT_XMLNS,
         user: data.user.clone(),
@@ -220,16 +255,179 @@ pub async fn fulfill<H: HttpClient>(
     let serialized =
         substitute_fulfillment_token(&serialize_xml(&raw_req)?, &data.fulfillment_token);
 
-    let response = parse_response(
+    parse_fulfill_response(
         http_client
             .request(make_post_serialized(operator_url, "/Fulfill", &serialized)?)
             .await?,
-    )?;
+    )
+}
 
-    Ok(response)
+fn parse_fulfill_response(response: HttpResponse) -> crate::Result<FulfillResponse> {
+    let (envelope, raw): (Envelope, _) = parse_response_raw(response)?;
+
+    let root = Element::parse(raw.as_bytes())?;
+    let notifications = make_notifications(&envelope.fulfillmen_result.notify, &root)
+        .context("could not extract fulfillment notifications")?;
+
+    let mut resource_items = Vec::new();
+    find_elements(&root, "resourceItemInfo", &mut resource_items);
+
+    Ok(FulfillResponse {
+        envelope,
+        notifications,
+        resource_items: resource_items.into_iter().cloned().collect(),
+    })
 }
 
 fn substitute_fulfillment_token(s: &str, token: &str) -> String {
-    const PLACEHOLDER_ELEMENT: &str = "<fulfillment_token_placeholder/>";
-    s.replacen(PLACEHOLDER_ELEMENT, token, 1)
+    substitute_placeholder(s, "fulfillment_token_placeholder", token)
+}
+
+/// Request for a fresh copy of a resource the user already owns, identified by its license token.
+/// The operator responds with a fulfillment result, as for [`fulfill`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "adept:downloadAgain")]
+struct DownloadAgain {
+    #[serde(rename = "@xmlns:adept")]
+    pub adept_xmlns: &'static str,
+    #[serde(rename = "adept:user")]
+    pub user: String,
+    #[serde(rename = "adept:device")]
+    pub device: String,
+    #[serde(rename = "adept:fulfillment")]
+    pub fulfillment: String,
+    #[serde(rename = "adept:resource")]
+    pub resource: String,
+    pub license_token_placeholder: (),
+    #[serde(rename = "adept:nonce")]
+    pub nonce: String,
+    #[serde(rename = "adept:expiration")]
+    pub expiration: String,
+    #[serde(rename = "adept:signature")]
+    pub signature: Option<String>,
+}
+
+impl_set_signature!(DownloadAgain, signature);
+
+pub struct DownloadAgainData {
+    pub user: String,
+    pub device: String,
+    pub fulfillment: String,
+    pub resource: String,
+    /// Raw `licenseToken` element.
+    pub license_token: String,
+    pub nonce: String,
+    pub expiration: String,
+}
+
+pub async fn download_again<H: HttpClient>(
+    http_client: &H,
+    signer: &Signer,
+    operator_url: &str,
+    data: DownloadAgainData,
+) -> crate::Result<FulfillResponse> {
+    let mut raw_req = DownloadAgain {
+        adept_xmlns: ADEPT_XMLNS,
+        user: data.user,
+        device: data.device,
+        fulfillment: data.fulfillment,
+        resource: data.resource,
+        license_token_placeholder: (),
+        nonce: data.nonce,
+        expiration: data.expiration,
+        signature: None,
+    };
+
+    let serialized_raw = substitute_license_token(&serialize_xml(&raw_req)?, &data.license_token);
+
+    let signature = compute_signature_raw(signer, &serialized_raw)?;
+    raw_req.set_signature(signature);
+
+    let serialized = substitute_license_token(&serialize_xml(&raw_req)?, &data.license_token);
+
+    parse_fulfill_response(
+        http_client
+            .request(make_post_serialized(
+                operator_url,
+                "/DownloadAgain",
+                &serialized,
+            )?)
+            .await?,
+    )
+}
+
+fn substitute_license_token(s: &str, token: &str) -> String {
+    substitute_placeholder(s, "license_token_placeholder", token)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "adept:loanReturn")]
+pub struct LoanReturn {
+    #[serde(rename = "@xmlns:adept")]
+    pub adept_xmlns: &'static str,
+    #[serde(rename = "adept:user")]
+    pub user: String,
+    #[serde(rename = "adept:device")]
+    pub device: String,
+    #[serde(rename = "adept:loan")]
+    pub loan: String,
+    #[serde(rename = "adept:nonce")]
+    pub nonce: String,
+    #[serde(rename = "adept:expiration")]
+    pub expiration: String,
+    #[serde(rename = "adept:signature")]
+    pub signature: Option<String>,
+}
+
+impl_set_signature!(LoanReturn, signature);
+
+pub struct LoanReturnData {
+    pub user: String,
+    pub device: String,
+    pub loan: String,
+    pub nonce: String,
+    pub expiration: String,
+}
+
+/// The operator may respond with just a success element.
+#[derive(Debug, Clone, Deserialize)]
+struct LoanReturnResponse {
+    #[serde(rename = "loanReturnResult", default)]
+    result: Option<LoanReturnResult>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct LoanReturnResult {
+    #[serde(default)]
+    notify: Vec<FulfillmentNotify>,
+}
+
+pub async fn return_loan<H: HttpClient>(
+    http_client: &H,
+    signer: &Signer,
+    operator_url: &str,
+    data: LoanReturnData,
+) -> crate::Result<Vec<Notification>> {
+    let req = compute_signature(
+        signer,
+        LoanReturn {
+            adept_xmlns: ADEPT_XMLNS,
+            user: data.user,
+            device: data.device,
+            loan: data.loan,
+            nonce: data.nonce,
+            expiration: data.expiration,
+            signature: None,
+        },
+    )?;
+
+    let (response, raw): (LoanReturnResponse, _) = parse_response_raw(
+        http_client
+            .request(make_post(operator_url, "/LoanReturn", &req)?)
+            .await?,
+    )?;
+
+    let notify = response.result.map(|x| x.notify).unwrap_or_default();
+    let root = Element::parse(raw.as_bytes())?;
+    make_notifications(&notify, &root).context("could not extract loan return notifications")
 }
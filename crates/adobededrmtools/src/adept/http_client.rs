@@ -21,6 +21,103 @@ pub struct HttpResponse {
     pub body: Vec<u8>,
 }
 
+/// Error returned by an [`HttpClient`] implementation, kept as is in
+/// [`Error::Transport`](crate::Error::Transport) so that callers can downcast it.
+pub type HttpError = Box<dyn std::error::Error + Send + Sync>;
+
 pub trait HttpClient {
-    fn request(&self, request: HttpRequest) -> impl Future<Output = anyhow::Result<HttpResponse>>;
+    fn request(
+        &self,
+        request: HttpRequest,
+    ) -> impl Future<Output = Result<HttpResponse, HttpError>>;
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::{cell::RefCell, collections::VecDeque};
+
+    use super::{HttpClient, HttpError, HttpRequest, HttpResponse};
+
+    /// Request received by [`MockHttpClient`].
+    #[derive(Clone)]
+    pub struct RecordedRequest {
+        pub url: String,
+        pub body: String,
+    }
+
+    type Handler = Box<dyn Fn(&str) -> String>;
+
+    /// Responds to requests with the responses queued for their URL, in order.
+    ///
+    /// Requests without a queued response fail with a transport error.
+    #[derive(Default)]
+    pub struct MockHttpClient {
+        responses: RefCell<VecDeque<(String, Handler)>>,
+        requests: RefCell<Vec<RecordedRequest>>,
+    }
+
+    impl MockHttpClient {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Queues an ADEPT response to the next request to `url`.
+        pub fn respond(&self, url: &str, body: &str) -> &Self {
+            let body = body.to_string();
+            self.respond_with(url, move |_| body.clone())
+        }
+
+        /// Queues a response to the next request to `url`, made from the request body.
+        pub fn respond_with(&self, url: &str, handler: impl Fn(&str) -> String + 'static) -> &Self {
+            self.responses
+                .borrow_mut()
+                .push_back((url.to_string(), Box::new(handler)));
+            self
+        }
+
+        /// Queues an ADEPT error response to the next request to `url`.
+        pub fn respond_error(&self, url: &str, code: &str) -> &Self {
+            self.respond(url, &format!(r#"<error data="{} {}"/>"#, code, url))
+        }
+
+        pub fn requests(&self) -> Vec<RecordedRequest> {
+            self.requests.borrow().clone()
+        }
+
+        /// URLs of the requests received so far.
+        pub fn urls(&self) -> Vec<String> {
+            self.requests
+                .borrow()
+                .iter()
+                .map(|x| x.url.clone())
+                .collect()
+        }
+    }
+
+    impl HttpClient for MockHttpClient {
+        async fn request(&self, request: HttpRequest) -> Result<HttpResponse, HttpError> {
+            let body = request
+                .content
+                .map(|x| String::from_utf8_lossy(&x.content).into_owned())
+                .unwrap_or_default();
+            self.requests.borrow_mut().push(RecordedRequest {
+                url: request.url.clone(),
+                body: body.clone(),
+            });
+
+            let mut responses = self.responses.borrow_mut();
+            let position = responses
+                .iter()
+                .position(|(url, _)| *url == request.url)
+                .ok_or_else(|| format!("unexpected request to {}", request.url))?;
+            let (_, handler) = responses.remove(position).unwrap();
+            let body = handler(&body);
+
+            Ok(HttpResponse {
+                response_code: 200,
+                content_type: "application/vnd.adobe.adept+xml".to_string(),
+                body: body.into_bytes(),
+            })
+        }
+    }
 }
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use xmltree::Element;
+
+use super::xml::element_text;
+
+/// Dublin Core metadata of the resource.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResourceMetadata {
+    pub title: Option<String>,
+    pub creator: Option<String>,
+    pub publisher: Option<String>,
+    pub language: Option<String>,
+    pub format: Option<String>,
+    pub identifier: Option<String>,
+}
+
+/// Parses the `metadata` element of an ACSM or a fulfillment response.
+pub fn parse_metadata(element: &Element) -> ResourceMetadata {
+    let text = |name: &str| element.get_child(name).map(element_text);
+
+    ResourceMetadata {
+        title: text("title"),
+        creator: text("creator"),
+        publisher: text("publisher"),
+        language: text("language"),
+        format: text("format"),
+        identifier: text("identifier"),
+    }
+}
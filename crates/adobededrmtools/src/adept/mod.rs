@@ -1,6 +1,10 @@
 mod acsm;
 mod activation;
 mod fulfillment;
+mod metadata;
+mod notify;
+mod permissions;
+mod records;
 mod request;
 mod response;
 mod signature;
@@ -13,8 +17,14 @@ const USERAGENT: &str = "book2png";
 const CONTENT_TYPE: &str = "application/vnd.adobe.adept+xml";
 const ADEPT_XMLNS: &str = "http://ns.adobe.com/adept";
 
-pub use acsm::Acsm;
+pub use acsm::{Acsm, FulfillmentType};
 pub use activation::*;
 pub use fulfillment::*;
 pub use http_client::HttpClient;
+pub use metadata::*;
+pub use notify::*;
+pub use permissions::*;
+pub use records::*;
+pub use signature::verify_element_signature;
 pub use types::*;
+pub use xml::write_element;
@@ -0,0 +1,190 @@
+use adobededrmtools_crypto::Signer;
+use serde::Serialize;
+use xmltree::Element;
+
+use super::ADEPT_XMLNS;
+use super::HttpClient;
+use super::fulfillment::FulfillmentNotify;
+use super::request::make_post_serialized;
+use super::response::parse_response;
+use super::signature::{SetSignature, compute_signature_raw, impl_set_signature};
+use super::xml::{find_elements, serialize_xml, substitute_placeholder, write_element};
+use crate::Error;
+
+// Example of a notification in the fulfillment response:
+// <notify critical="yes">
+//   <notifyURL>http://acs.example.com/fulfillment/Notify</notifyURL>
+//   <body>
+//     <fulfillment>...</fulfillment>
+//     <transaction>...</transaction>
+//     ...
+//   </body>
+// </notify>
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub notify_url: String,
+    pub critical: bool,
+    /// Raw XML of the `body` element, to be sent back as is.
+    pub body: Option<String>,
+}
+
+/// Notifications asked for by the `notify` elements of a response.
+///
+/// The bodies are sent back as is, so they are taken from the raw `notify` elements of
+/// `response`, found in the same order as `notify`.
+pub fn make_notifications(
+    notify: &[FulfillmentNotify],
+    response: &Element,
+) -> crate::Result<Vec<Notification>> {
+    let mut notify_elements = Vec::new();
+    find_elements(response, "notify", &mut notify_elements);
+
+    if notify_elements.len() != notify.len() {
+        return Err(Error::Protocol(format!(
+            "expected {} notify elements, found {}",
+            notify.len(),
+            notify_elements.len()
+        )));
+    }
+
+    notify
+        .iter()
+        .zip(notify_elements)
+        .map(|(notify, element)| {
+            Ok(Notification {
+                notify_url: notify.notify_url.trim().to_string(),
+                critical: matches!(notify.critical.as_deref(), Some("yes" | "true")),
+                body: element.get_child("body").map(write_element).transpose()?,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename = "adept:notification")]
+struct AdeptNotification {
+    #[serde(rename = "@xmlns:adept")]
+    pub adept_xmlns: &'static str,
+    #[serde(rename = "adept:user")]
+    pub user: String,
+    #[serde(rename = "adept:device")]
+    pub device: String,
+    pub body_placeholder: (),
+    #[serde(rename = "adept:nonce")]
+    pub nonce: String,
+    #[serde(rename = "adept:expiration")]
+    pub expiration: String,
+    #[serde(rename = "adept:signature")]
+    pub signature: Option<String>,
+}
+
+impl_set_signature!(AdeptNotification, signature);
+
+pub struct NotifyData {
+    pub user: String,
+    pub device: String,
+    pub body: String,
+    pub nonce: String,
+    pub expiration: String,
+}
+
+pub async fn notify<H: HttpClient>(
+    http_client: &H,
+    signer: &Signer,
+    notify_url: &str,
+    data: NotifyData,
+) -> crate::Result<()> {
+    const PLACEHOLDER: &str = "body_placeholder";
+
+    let mut raw_req = AdeptNotification {
+        adept_xmlns: ADEPT_XMLNS,
+        user: data.user,
+        device: data.device,
+        body_placeholder: (),
+        nonce: data.nonce,
+        expiration: data.expiration,
+        signature: None,
+    };
+
+    let serialized_raw = substitute_placeholder(&serialize_xml(&raw_req)?, PLACEHOLDER, &data.body);
+
+    let signature = compute_signature_raw(signer, &serialized_raw)?;
+    raw_req.set_signature(signature);
+
+    let serialized = substitute_placeholder(&serialize_xml(&raw_req)?, PLACEHOLDER, &data.body);
+
+    parse_response::<()>(
+        http_client
+            .request(make_post_serialized(notify_url, "", &serialized)?)
+            .await?,
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use xmltree::Element;
+
+    use super::make_notifications;
+    use crate::adept::fulfillment::FulfillmentNotify;
+
+    #[test]
+    fn test_make_notifications() {
+        const RESPONSE: &str = r#"<?xml version="1.0"?>
+<envelope xmlns="http://ns.adobe.com/adept">
+  <fulfillmentResult>
+    <fulfillment>urn:uuid:0b3fbd1f-1d4b-4ba5-9f7b-4d2bdfbcc0a5</fulfillment>
+    <returnable>false</returnable>
+    <initial>true</initial>
+    <notify critical="yes">
+      <notifyURL>http://acs.example.com/fulfillment/Notify</notifyURL>
+      <body>
+        <fulfillment>urn:uuid:0b3fbd1f-1d4b-4ba5-9f7b-4d2bdfbcc0a5</fulfillment>
+        <transaction>61777-38641</transaction>
+      </body>
+    </notify>
+    <notify>
+      <notifyURL>http://distributor.example.com/Notify</notifyURL>
+    </notify>
+  </fulfillmentResult>
+</envelope>"#;
+
+        let notify = [
+            FulfillmentNotify {
+                critical: Some("yes".to_string()),
+                notify_url: "http://acs.example.com/fulfillment/Notify".to_string(),
+            },
+            FulfillmentNotify {
+                critical: None,
+                notify_url: "http://distributor.example.com/Notify".to_string(),
+            },
+        ];
+        let root = Element::parse(RESPONSE.as_bytes()).unwrap();
+        let notifications = make_notifications(&notify, &root).expect("make_notifications failed");
+
+        // The raw elements must match the deserialized ones.
+        assert!(make_notifications(&notify[..1], &root).is_err());
+        assert_eq!(notifications.len(), 2);
+
+        assert_eq!(
+            notifications[0].notify_url,
+            "http://acs.example.com/fulfillment/Notify"
+        );
+        assert!(notifications[0].critical);
+        assert_eq!(
+            notifications[0].body.as_deref(),
+            Some(
+                r#"<body xmlns="http://ns.adobe.com/adept"><fulfillment>urn:uuid:0b3fbd1f-1d4b-4ba5-9f7b-4d2bdfbcc0a5</fulfillment><transaction>61777-38641</transaction></body>"#
+            )
+        );
+
+        assert_eq!(
+            notifications[1].notify_url,
+            "http://distributor.example.com/Notify"
+        );
+        assert!(!notifications[1].critical);
+        assert!(notifications[1].body.is_none());
+    }
+}
@@ -0,0 +1,155 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use xmltree::Element;
+
+use super::xml::{element_text, parse_datetime};
+use crate::error::Context;
+
+/// Rights granted by a license, as listed in the `permissions` element.
+///
+/// A right may be granted several times, e.g. once per device, so every element is kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Permissions {
+    #[serde(default)]
+    pub display: Vec<Permission>,
+    #[serde(default)]
+    pub excerpt: Vec<Permission>,
+    #[serde(default)]
+    pub print: Vec<Permission>,
+    #[serde(default)]
+    pub play: Vec<Permission>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Permission {
+    /// Device the permission is bound to.
+    pub device: Option<String>,
+    /// Time the permission expires at, e.g. the end of a loan.
+    pub until: Option<DateTime<Utc>>,
+    pub count: Option<PermissionCount>,
+}
+
+/// Limit on the number of uses, e.g. printed pages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionCount {
+    pub initial: u32,
+    pub max: Option<u32>,
+    /// Seconds after which one more use is granted, up to `max`.
+    pub increment_interval: Option<u32>,
+}
+
+/// Parses the `permissions` element. Unknown permissions and invalid limits are dropped with a
+/// warning rather than failing, since the resource can be used without knowing them.
+pub fn parse_permissions(element: &Element) -> Permissions {
+    let mut permissions = Permissions::default();
+
+    for child in element.children.iter().filter_map(|x| x.as_element()) {
+        let list = match child.name.as_str() {
+            "display" => &mut permissions.display,
+            "excerpt" => &mut permissions.excerpt,
+            "print" => &mut permissions.print,
+            "play" => &mut permissions.play,
+            name => {
+                log::warn!("Ignoring unknown permission: {}", name);
+                continue;
+            }
+        };
+        list.push(parse_permission(&child.name, child));
+    }
+
+    permissions
+}
+
+fn parse_permission(name: &str, element: &Element) -> Permission {
+    let until = element.get_child("until").and_then(|x| {
+        parse_datetime(&element_text(x))
+            .inspect_err(|err| log::warn!("Ignoring invalid {} permission until: {}", name, err))
+            .ok()
+    });
+    let count = element.get_child("count").and_then(|x| {
+        parse_count(x)
+            .inspect_err(|err| log::warn!("Ignoring invalid {} permission count: {}", name, err))
+            .ok()
+    });
+
+    Permission {
+        device: element.get_child("device").map(element_text),
+        until,
+        count,
+    }
+}
+
+fn parse_count(element: &Element) -> crate::Result<PermissionCount> {
+    let attribute = |name: &str| {
+        element
+            .attributes
+            .get(name)
+            .map(|x| {
+                x.trim()
+                    .parse::<u32>()
+                    .ok()
+                    .with_context(|| format!("count has invalid {}", name))
+            })
+            .transpose()
+    };
+
+    Ok(PermissionCount {
+        initial: attribute("initial")?.context("count has no initial")?,
+        max: attribute("max")?,
+        increment_interval: attribute("incrementInterval")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use xmltree::Element;
+
+    use super::parse_permissions;
+
+    #[test]
+    fn test_parse_permissions() {
+        const PERMISSIONS: &str = r#"<permissions xmlns="http://ns.adobe.com/adept">
+  <display>
+    <device>urn:uuid:00000000-0000-0000-0000-000000000001</device>
+    <until>2025-03-15T18:00:00Z</until>
+  </display>
+  <display>
+    <device>urn:uuid:00000000-0000-0000-0000-000000000002</device>
+    <until>the ides of march</until>
+  </display>
+  <lend/>
+  <print>
+    <count initial="-1" max="20"/>
+  </print>
+  <play/>
+  <play>
+    <count initial="3"/>
+  </play>
+</permissions>"#;
+
+        let permissions = parse_permissions(&Element::parse(PERMISSIONS.as_bytes()).unwrap());
+
+        assert_eq!(permissions.display.len(), 2);
+        assert_eq!(
+            permissions.display[0].until.map(|x| x.to_rfc3339()),
+            Some("2025-03-15T18:00:00+00:00".to_string())
+        );
+        // The invalid limit is dropped, the permission itself is kept.
+        assert_eq!(
+            permissions.display[1].device.as_deref(),
+            Some("urn:uuid:00000000-0000-0000-0000-000000000002")
+        );
+        assert_eq!(permissions.display[1].until, None);
+
+        assert!(permissions.excerpt.is_empty());
+        assert_eq!(permissions.print.len(), 1);
+        assert_eq!(permissions.print[0].count, None);
+
+        assert_eq!(permissions.play.len(), 2);
+        assert_eq!(permissions.play[0].count, None);
+        assert_eq!(
+            permissions.play[1].count.as_ref().map(|x| x.initial),
+            Some(3)
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use serde::Serialize;
+use xmltree::Element;
+
+use super::ADEPT_XMLNS;
+use super::xml::{child, child_text, element_text, find_elements};
+use crate::error::Context;
+
+// Activation records as stored by ADE-based readers in `.adobe-digital-editions`.
+// The layout follows the files written by ADE and libgourou.
+// Parsing only looks at local names, since the prefixes differ between writers.
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename = "activationInfo")]
+struct ActivationInfoRecord {
+    #[serde(rename = "@xmlns")]
+    pub xmlns: &'static str,
+    #[serde(rename = "@xmlns:adept")]
+    pub adept_xmlns: &'static str,
+    #[serde(rename = "adept:activationServiceInfo")]
+    pub activation_service_info: ActivationServiceInfoRecord,
+    #[serde(rename = "adept:credentials")]
+    pub credentials: CredentialsRecord,
+    #[serde(rename = "adept:activationToken")]
+    pub activation_token: ActivationTokenRecord,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActivationServiceInfoRecord {
+    #[serde(rename = "adept:authURL")]
+    pub auth_url: String,
+    #[serde(rename = "adept:userInfoURL")]
+    pub user_info_url: String,
+    #[serde(rename = "adept:activationURL")]
+    pub activation_url: String,
+    #[serde(rename = "adept:certificate")]
+    pub certificate: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CredentialsRecord {
+    #[serde(rename = "adept:user")]
+    pub user: String,
+    #[serde(rename = "adept:username", skip_serializing_if = "Option::is_none")]
+    pub username: Option<UsernameRecord>,
+    #[serde(rename = "adept:pkcs12")]
+    pub pkcs12: String,
+    #[serde(rename = "adept:licenseCertificate")]
+    pub license_certificate: String,
+    #[serde(rename = "adept:privateLicenseKey")]
+    pub private_license_key: String,
+    #[serde(rename = "adept:authenticationCertificate")]
+    pub authentication_certificate: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsernameRecord {
+    #[serde(rename = "@method")]
+    pub method: String,
+    #[serde(rename = "$text")]
+    pub username: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActivationTokenRecord {
+    #[serde(rename = "adept:device")]
+    pub device: String,
+    #[serde(rename = "adept:fingerprint")]
+    pub fingerprint: String,
+    #[serde(rename = "adept:deviceType")]
+    pub device_type: String,
+    #[serde(rename = "adept:activationURL")]
+    pub activation_url: String,
+    #[serde(rename = "adept:user")]
+    pub user: String,
+    #[serde(rename = "adept:signature")]
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename = "adept:deviceInfo")]
+struct DeviceInfoRecord {
+    #[serde(rename = "@xmlns:adept")]
+    pub adept_xmlns: &'static str,
+    #[serde(rename = "adept:deviceClass")]
+    pub device_class: String,
+    #[serde(rename = "adept:deviceSerial")]
+    pub device_serial: String,
+    #[serde(rename = "adept:deviceName")]
+    pub device_name: String,
+    #[serde(rename = "adept:deviceType")]
+    pub device_type: String,
+    #[serde(rename = "adept:version")]
+    pub versions: Vec<VersionRecord>,
+    #[serde(rename = "adept:fingerprint")]
+    pub fingerprint: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionRecord {
+    #[serde(rename = "@name")]
+    pub name: String,
+    #[serde(rename = "@value")]
+    pub value: String,
+}
+
+pub struct ActivationRecordData {
+    pub activation_service_info: ActivationServiceInfoRecord,
+    pub credentials: CredentialsRecord,
+    pub activation_token: ActivationTokenRecord,
+}
+
+/// Serializes the contents of `activation.xml`.
+pub fn serialize_activation_record(data: ActivationRecordData) -> crate::Result<String> {
+    serialize_record(&ActivationInfoRecord {
+        xmlns: ADEPT_XMLNS,
+        adept_xmlns: ADEPT_XMLNS,
+        activation_service_info: data.activation_service_info,
+        credentials: data.credentials,
+        activation_token: data.activation_token,
+    })
+}
+
+pub struct DeviceRecordData {
+    pub device_class: String,
+    pub device_serial: String,
+    pub device_name: String,
+    pub device_type: String,
+    pub versions: Vec<VersionRecord>,
+    pub fingerprint: String,
+}
+
+/// Serializes the contents of `device.xml`.
+pub fn serialize_device_record(data: DeviceRecordData) -> crate::Result<String> {
+    serialize_record(&DeviceInfoRecord {
+        adept_xmlns: ADEPT_XMLNS,
+        device_class: data.device_class,
+        device_serial: data.device_serial,
+        device_name: data.device_name,
+        device_type: data.device_type,
+        versions: data.versions,
+        fingerprint: data.fingerprint,
+    })
+}
+
+/// Parses the contents of `activation.xml`.
+pub fn parse_activation_record(xml: &str) -> crate::Result<ActivationRecordData> {
+    let root = Element::parse(xml.as_bytes()).context("could not parse activation record xml")?;
+
+    let service = child(&root, "activationServiceInfo")?;
+    let credentials = child(&root, "credentials")?;
+    let token = child(&root, "activationToken")?;
+
+    Ok(ActivationRecordData {
+        activation_service_info: ActivationServiceInfoRecord {
+            auth_url: child_text(service, "authURL")?,
+            user_info_url: child_text(service, "userInfoURL")?,
+            activation_url: child_text(service, "activationURL")?,
+            certificate: child_text(service, "certificate")?,
+        },
+        credentials: CredentialsRecord {
+            user: child_text(credentials, "user")?,
+            username: parse_username(credentials)?,
+            pkcs12: child_text(credentials, "pkcs12")?,
+            license_certificate: child_text(credentials, "licenseCertificate")?,
+            private_license_key: child_text(credentials, "privateLicenseKey")?,
+            authentication_certificate: child_text(credentials, "authenticationCertificate")?,
+        },
+        activation_token: ActivationTokenRecord {
+            device: child_text(token, "device")?,
+            fingerprint: child_text(token, "fingerprint")?,
+            device_type: child_text(token, "deviceType")?,
+            activation_url: child_text(token, "activationURL")?,
+            user: child_text(token, "user")?,
+            signature: child_text(token, "signature")?,
+        },
+    })
+}
+
+/// User credentials needed to decrypt resources, i.e. everything but the device-protected keys.
+pub struct LicenseCredentialsRecord {
+    pub user: String,
+    pub username: Option<UsernameRecord>,
+    pub private_license_key: String,
+}
+
+/// Finds the license credentials anywhere in an activation document. Unlike
+/// [`parse_activation_record`], doesn't require the rest of the activation to be present.
+pub fn parse_license_credentials(xml: &str) -> crate::Result<LicenseCredentialsRecord> {
+    let root = Element::parse(xml.as_bytes()).context("could not parse activation xml")?;
+
+    let credentials = if root.name == "credentials" {
+        &root
+    } else {
+        let mut found = Vec::new();
+        find_elements(&root, "credentials", &mut found);
+        found
+            .into_iter()
+            .next()
+            .context("no credentials element in activation xml")?
+    };
+
+    Ok(LicenseCredentialsRecord {
+        user: child_text(credentials, "user")?,
+        username: parse_username(credentials)?,
+        private_license_key: child_text(credentials, "privateLicenseKey")?,
+    })
+}
+
+/// Parses the contents of `device.xml`.
+pub fn parse_device_record(xml: &str) -> crate::Result<DeviceRecordData> {
+    let root = Element::parse(xml.as_bytes()).context("could not parse device record xml")?;
+
+    let versions = root
+        .children
+        .iter()
+        .filter_map(|x| x.as_element())
+        .filter(|x| x.name == "version")
+        .map(|x| -> crate::Result<_> {
+            let attribute = |name: &str| {
+                x.attributes
+                    .get(name)
+                    .cloned()
+                    .with_context(|| format!("version has no {} attribute", name))
+            };
+
+            Ok(VersionRecord {
+                name: attribute("name")?,
+                value: attribute("value")?,
+            })
+        })
+        .collect::<crate::Result<_>>()?;
+
+    Ok(DeviceRecordData {
+        device_class: child_text(&root, "deviceClass")?,
+        device_serial: child_text(&root, "deviceSerial")?,
+        device_name: child_text(&root, "deviceName")?,
+        device_type: child_text(&root, "deviceType")?,
+        versions,
+        fingerprint: child_text(&root, "fingerprint")?,
+    })
+}
+
+fn parse_username(credentials: &Element) -> crate::Result<Option<UsernameRecord>> {
+    credentials
+        .get_child("username")
+        .map(|x| -> crate::Result<_> {
+            Ok(UsernameRecord {
+                method: x
+                    .attributes
+                    .get("method")
+                    .context("username has no method")?
+                    .clone(),
+                username: element_text(x),
+            })
+        })
+        .transpose()
+}
+
+fn serialize_record<T: Serialize>(record: &T) -> crate::Result<String> {
+    let mut buf = String::from("<?xml version=\"1.0\"?>\n");
+    let mut serializer = quick_xml::se::Serializer::new(&mut buf);
+    serializer.indent(' ', 2);
+    record.serialize(serializer)?;
+    buf.push('\n');
+    Ok(buf)
+}
@@ -1,4 +1,3 @@
-use anyhow::Context;
 use serde::Serialize;
 
 use super::{
@@ -6,6 +5,7 @@ use super::{
     http_client::{HttpContent, HttpMethod, HttpRequest},
     xml::serialize_xml,
 };
+use crate::error::Context;
 
 fn make_url(base: &str, path: &str) -> String {
     format!("{}{}", base, path)
@@ -20,12 +20,12 @@ pub fn make_get(base: &str, path: &str) -> HttpRequest {
     }
 }
 
-pub fn make_post<T: Serialize>(base: &str, path: &str, content: &T) -> anyhow::Result<HttpRequest> {
+pub fn make_post<T: Serialize>(base: &str, path: &str, content: &T) -> crate::Result<HttpRequest> {
     let content = serialize_xml(content).context("could not serialize request")?;
     make_post_serialized(base, path, &content)
 }
 
-pub fn make_post_serialized(base: &str, path: &str, content: &str) -> anyhow::Result<HttpRequest> {
+pub fn make_post_serialized(base: &str, path: &str, content: &str) -> crate::Result<HttpRequest> {
     log::debug!("serialized: {}", content);
 
     let req = HttpRequest {
@@ -1,25 +1,24 @@
-use anyhow::Context;
 use serde::{Deserialize, de::DeserializeOwned};
 
 use super::{AdeptError, http_client::HttpResponse, xml::deserialize_xml};
+use crate::{Error, error::Context};
 
-fn parse_response_inner(response: HttpResponse) -> anyhow::Result<String> {
+fn parse_response_inner(response: HttpResponse) -> crate::Result<String> {
     // All the successfull requests have status code 200.
     if response.response_code != 200 {
-        return Err(anyhow::anyhow!(
+        return Err(Error::Protocol(format!(
             "unsuccessful http request: status={}",
             response.response_code
-        ));
+        )));
     }
 
     const EXPECTED_CONTENT_TYPE: &str = "application/vnd.adobe.adept+xml";
 
     if response.content_type != EXPECTED_CONTENT_TYPE {
-        return Err(anyhow::anyhow!(
+        return Err(Error::Protocol(format!(
             "response content type mismatch. expected: {}, actual: {}",
-            EXPECTED_CONTENT_TYPE,
-            response.content_type
-        ));
+            EXPECTED_CONTENT_TYPE, response.content_type
+        )));
     }
 
     // Convert the body to string.
@@ -37,29 +36,23 @@ pub struct AdeptErrorDto {
     data: String,
 }
 
-fn parse_adept_error(dto: AdeptErrorDto) -> anyhow::Result<AdeptError> {
+fn parse_adept_error(dto: AdeptErrorDto) -> crate::Result<AdeptError> {
     // Example: E_ADEPT_MISSING_REQUEST_CONTENT_TYPE http://adeactivate.adobe.com/adept/SignInDirect
 
     let mut parts = dto.data.split(" ");
 
     let Some(name) = parts.next() else {
         // If no space, then the whole error is the name.
-        return Ok(AdeptError {
-            name: dto.data,
-            args: Vec::new(),
-        });
+        return Ok(AdeptError::new(dto.data, Vec::new()));
     };
 
     let args = parts.map(|x| x.to_string()).collect();
 
-    Ok(AdeptError {
-        name: name.to_string(),
-        args,
-    })
+    Ok(AdeptError::new(name.to_string(), args))
 }
 
-fn try_parse_as_error(response: &str) -> anyhow::Result<Option<AdeptError>> {
-    let Ok(parse_error) = deserialize_xml(&response) else {
+fn try_parse_as_error(response: &str) -> crate::Result<Option<AdeptError>> {
+    let Ok(parse_error) = deserialize_xml(response) else {
         return Ok(None);
     };
 
@@ -67,7 +60,14 @@ fn try_parse_as_error(response: &str) -> anyhow::Result<Option<AdeptError>> {
     Ok(Some(adept_error))
 }
 
-pub fn parse_response<T: DeserializeOwned>(response: HttpResponse) -> anyhow::Result<T> {
+pub fn parse_response<T: DeserializeOwned>(response: HttpResponse) -> crate::Result<T> {
+    parse_response_raw(response).map(|(parsed, _)| parsed)
+}
+
+/// Same as [`parse_response`], but also returns the raw response.
+pub fn parse_response_raw<T: DeserializeOwned>(
+    response: HttpResponse,
+) -> crate::Result<(T, String)> {
     let response = parse_response_inner(response)?;
     log::debug!("response: {}", response);
 
@@ -80,5 +80,6 @@ pub fn parse_response<T: DeserializeOwned>(response: HttpResponse) -> anyhow::Re
 
     // If this fails as well, then the server returned neither an error, nor the expected response,
     // which may suggest that the API has changed
-    deserialize_xml(&response).context("could not deserialize xml")
+    let parsed = deserialize_xml(&response).context("could not deserialize xml")?;
+    Ok((parsed, response))
 }
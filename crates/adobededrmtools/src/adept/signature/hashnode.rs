@@ -35,12 +35,16 @@ impl<U: Updater> Hasher for U {
     }
 }
 
-pub fn hash_xml<H: Hasher>(hasher: &mut H, xml: &str) -> anyhow::Result<()> {
+pub fn hash_xml<H: Hasher>(hasher: &mut H, xml: &str) -> crate::Result<()> {
     let doc = Element::parse(xml.as_bytes())?;
-    hash_element_inner(hasher, &HasherElement(&doc));
+    hash_element(hasher, &doc);
     Ok(())
 }
 
+pub fn hash_element<H: Hasher>(hasher: &mut H, element: &Element) {
+    hash_element_inner(hasher, &HasherElement(element));
+}
+
 enum HasherNode<'a> {
     Element(HasherElement<'a>),
     Text(&'a str),
@@ -50,21 +54,21 @@ struct HasherElement<'a>(&'a Element);
 
 impl<'a> HasherElement<'a> {
     pub fn ns(&self) -> Option<&str> {
-        self.0.namespace.as_ref().map(|x| x.as_str())
+        self.0.namespace.as_deref()
     }
 
     pub fn name(&self) -> &str {
         &self.0.name
     }
 
-    pub fn attributes(&self) -> impl Iterator<Item = Attribute> {
+    pub fn attributes(&self) -> impl Iterator<Item = Attribute<'_>> {
         self.0
             .attributes
             .iter()
             .map(|(k, v)| Attribute { name: k, value: v })
     }
 
-    pub fn children(&self) -> impl Iterator<Item = HasherNode> {
+    pub fn children(&self) -> impl Iterator<Item = HasherNode<'_>> {
         self.0.children.iter().filter_map(|node| match node {
             xmltree::XMLNode::Element(element) => Some(HasherNode::Element(HasherElement(element))),
             xmltree::XMLNode::Text(text) => Some(HasherNode::Text(text)),
@@ -110,7 +114,6 @@ fn hash_element_inner<H: Hasher>(hasher: &mut H, element: &HasherElement) {
     // Push attributes
     let mut attrs: Vec<Attribute> = element
         .attributes()
-        .into_iter()
         .filter(|attr| !attr.name().starts_with("xmlns"))
         .collect();
     attrs.sort_by(|a, b| a.name().cmp(b.name()));
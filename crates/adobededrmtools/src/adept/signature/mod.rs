@@ -1,5 +1,6 @@
-use adobededrmtools_crypto::{Sha1, Signer};
+use adobededrmtools_crypto::{Sha1, Signer, unb64, verify_with_cert};
 use serde::Serialize;
+use xmltree::Element;
 
 mod hashnode;
 
@@ -9,7 +10,7 @@ pub trait SetSignature {
     fn set_signature(&mut self, signature: String);
 }
 
-pub fn compute_signature<T>(signer: &Signer, mut value: T) -> anyhow::Result<T>
+pub fn compute_signature<T>(signer: &Signer, mut value: T) -> crate::Result<T>
 where
     T: SetSignature + Serialize,
 {
@@ -26,12 +27,27 @@ impl hashnode::Updater for Sha1Hasher {
     }
 }
 
-pub fn compute_signature_raw(signer: &Signer, serialized: &str) -> anyhow::Result<String> {
+pub fn compute_signature_raw(signer: &Signer, serialized: &str) -> crate::Result<String> {
     let mut hasher = Sha1Hasher(Sha1::new());
     hashnode::hash_xml(&mut hasher, serialized)?;
     Ok(signer.sign(&hasher.0.finalize()))
 }
 
+/// Checks the signature of a signed element, e.g. a license token, against `certificate`.
+pub fn verify_element_signature(
+    certificate: &[u8],
+    element: &Element,
+    signature: &str,
+) -> crate::Result<bool> {
+    let mut hasher = Sha1Hasher(Sha1::new());
+    hashnode::hash_element(&mut hasher, element);
+    Ok(verify_with_cert(
+        certificate,
+        &hasher.0.finalize(),
+        &unb64(signature)?,
+    )?)
+}
+
 macro_rules! impl_set_signature {
     ($typ:ty, $signature:ident) => {
         impl $crate::adept::signature::SetSignature for $typ {
@@ -43,3 +59,33 @@ macro_rules! impl_set_signature {
 }
 
 pub(crate) use impl_set_signature;
+
+#[cfg(test)]
+mod tests {
+    use adobededrmtools_crypto::make_signer;
+    use xmltree::Element;
+
+    use super::{compute_signature_raw, verify_element_signature};
+    use crate::testing::{USER_CERTIFICATE, USER_KEY};
+
+    const LICENSE_TOKEN: &str = r#"<licenseToken xmlns="http://ns.adobe.com/adept">
+  <resource>urn:uuid:00000000-0000-0000-0000-00000000000a</resource>
+  <encryptedKey keyInfo="urn:uuid:00000000-0000-0000-0000-000000000001">a2V5</encryptedKey>
+  <permissions><display/></permissions>
+</licenseToken>"#;
+
+    #[test]
+    fn test_verify_element_signature() {
+        crate::init_test_rand();
+
+        let signer = make_signer(USER_KEY).unwrap();
+        let signature = compute_signature_raw(&signer, LICENSE_TOKEN).unwrap();
+
+        let element = Element::parse(LICENSE_TOKEN.as_bytes()).unwrap();
+        assert!(verify_element_signature(USER_CERTIFICATE, &element, &signature).unwrap());
+
+        let tampered = LICENSE_TOKEN.replace("a2V5", "a2V6");
+        let element = Element::parse(tampered.as_bytes()).unwrap();
+        assert!(!verify_element_signature(USER_CERTIFICATE, &element, &signature).unwrap());
+    }
+}
@@ -1,15 +1,148 @@
 use std::{error::Error, fmt::Display};
 
+/// Error returned by an ADEPT service.
+///
+/// Survives the error context, so it can be found with [`Error::adept_error`](crate::Error::adept_error).
 #[derive(Debug)]
 pub struct AdeptError {
-    pub name: String,
+    pub code: AdeptErrorCode,
     pub args: Vec<String>,
 }
 
+impl AdeptError {
+    pub fn new(name: String, args: Vec<String>) -> Self {
+        Self {
+            code: AdeptErrorCode::from_name(&name),
+            args,
+        }
+    }
+
+    /// Name of the error as sent by the service, e.g. `E_ACT_TOO_MANY_ACTIVATIONS`.
+    pub fn name(&self) -> &str {
+        self.code.name()
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.code.is_retryable()
+    }
+}
+
 impl Display for AdeptError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("AdeptError({}, {:?})", self.name, self.args,))
+        f.write_fmt(format_args!("AdeptError({}, {:?})", self.name(), self.args,))
     }
 }
 
 impl Error for AdeptError {}
+
+/// Known ADEPT error codes.
+///
+/// Codes are matched by their exact name, any other code is kept in [`AdeptErrorCode::Other`].
+/// Only names seen in responses or handled by other ADEPT clients are recognized, more may be
+/// added in the future.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AdeptErrorCode {
+    /// The user has activated the maximum number of devices. `E_ACT_TOO_MANY_ACTIVATIONS`, as
+    /// documented in Adobe's troubleshooting for ADE.
+    TooManyActivations,
+    /// The request or token has expired, e.g. because of clock skew. `E_ADEPT_REQUEST_EXPIRED`,
+    /// as handled by acsm-calibre-plugin when fulfilling (`libadobeFulfill.py`).
+    RequestExpired,
+    /// The resource was fulfilled by another user. `E_LIC_ALREADY_FULFILLED_BY_ANOTHER_USER`,
+    /// as handled by acsm-calibre-plugin when fulfilling (`libadobeFulfill.py`).
+    AlreadyFulfilledByAnotherUser,
+    /// Wrong sign in credentials. `E_AUTH_FAILED`, as handled by acsm-calibre-plugin when
+    /// signing in (`libadobeAccount.py`).
+    AuthFailed,
+    /// `E_ADEPT_MISSING_REQUEST_CONTENT_TYPE`, returned by the activation service to requests
+    /// without a content type.
+    MissingRequestContentType,
+    Other(String),
+}
+
+impl AdeptErrorCode {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "E_ACT_TOO_MANY_ACTIVATIONS" => Self::TooManyActivations,
+            "E_ADEPT_REQUEST_EXPIRED" => Self::RequestExpired,
+            "E_LIC_ALREADY_FULFILLED_BY_ANOTHER_USER" => Self::AlreadyFulfilledByAnotherUser,
+            "E_AUTH_FAILED" => Self::AuthFailed,
+            "E_ADEPT_MISSING_REQUEST_CONTENT_TYPE" => Self::MissingRequestContentType,
+            name => Self::Other(name.to_string()),
+        }
+    }
+
+    /// Name of the code, the inverse of [`from_name`](Self::from_name).
+    pub fn name(&self) -> &str {
+        match self {
+            Self::TooManyActivations => "E_ACT_TOO_MANY_ACTIVATIONS",
+            Self::RequestExpired => "E_ADEPT_REQUEST_EXPIRED",
+            Self::AlreadyFulfilledByAnotherUser => "E_LIC_ALREADY_FULFILLED_BY_ANOTHER_USER",
+            Self::AuthFailed => "E_AUTH_FAILED",
+            Self::MissingRequestContentType => "E_ADEPT_MISSING_REQUEST_CONTENT_TYPE",
+            Self::Other(name) => name,
+        }
+    }
+
+    /// Whether sending the request again may succeed. Requests get a new nonce and expiration
+    /// every time they are made, so an expired request is worth retrying.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::RequestExpired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AdeptError, AdeptErrorCode};
+
+    #[test]
+    fn test_error_code_from_name() {
+        assert_eq!(
+            AdeptErrorCode::from_name("E_ACT_TOO_MANY_ACTIVATIONS"),
+            AdeptErrorCode::TooManyActivations
+        );
+        assert_eq!(
+            AdeptErrorCode::from_name("E_AUTH_FAILED"),
+            AdeptErrorCode::AuthFailed
+        );
+        assert_eq!(
+            AdeptErrorCode::from_name("E_STREAM_ERROR"),
+            AdeptErrorCode::Other("E_STREAM_ERROR".to_string())
+        );
+        // Similar names are not guessed to be the same error.
+        assert_eq!(
+            AdeptErrorCode::from_name("E_LIC_LICENSE_SIGNATURE_ERROR"),
+            AdeptErrorCode::Other("E_LIC_LICENSE_SIGNATURE_ERROR".to_string())
+        );
+        assert_eq!(
+            AdeptErrorCode::from_name("E_ADEPT_USER_UNKNOWN_DEVICE"),
+            AdeptErrorCode::Other("E_ADEPT_USER_UNKNOWN_DEVICE".to_string())
+        );
+        assert!(AdeptErrorCode::from_name("E_ADEPT_REQUEST_EXPIRED").is_retryable());
+        assert!(!AdeptErrorCode::from_name("E_AUTH_FAILED").is_retryable());
+    }
+
+    #[test]
+    fn test_error_name() {
+        for name in [
+            "E_ACT_TOO_MANY_ACTIVATIONS",
+            "E_ADEPT_REQUEST_EXPIRED",
+            "E_LIC_ALREADY_FULFILLED_BY_ANOTHER_USER",
+            "E_AUTH_FAILED",
+            "E_ADEPT_MISSING_REQUEST_CONTENT_TYPE",
+            "E_STREAM_ERROR",
+        ] {
+            assert_eq!(AdeptErrorCode::from_name(name).name(), name);
+        }
+
+        let err = AdeptError::new(
+            "E_ACT_TOO_MANY_ACTIVATIONS".to_string(),
+            vec!["https://adeactivate.adobe.com/adept/Activate".to_string()],
+        );
+        assert_eq!(
+            err.to_string(),
+            r#"AdeptError(E_ACT_TOO_MANY_ACTIVATIONS, ["https://adeactivate.adobe.com/adept/Activate"])"#
+        );
+    }
+}
@@ -1,9 +1,67 @@
+use chrono::{DateTime, Utc};
 use serde::{Serialize, de::DeserializeOwned};
+use xmltree::Element;
 
-pub fn serialize_xml<T: Serialize>(value: &T) -> anyhow::Result<String> {
+use crate::error::Context;
+
+pub fn serialize_xml<T: Serialize>(value: &T) -> crate::Result<String> {
     Ok(quick_xml::se::to_string(value)?)
 }
 
-pub fn deserialize_xml<T: DeserializeOwned>(s: &str) -> anyhow::Result<T> {
+pub fn deserialize_xml<T: DeserializeOwned>(s: &str) -> crate::Result<T> {
     Ok(quick_xml::de::from_str(s)?)
 }
+
+/// Replaces the first empty `name` element in `s` with `replacement`.
+/// Used to embed raw XML into serialized requests.
+pub fn substitute_placeholder(s: &str, name: &str, replacement: &str) -> String {
+    s.replacen(&format!("<{}/>", name), replacement, 1)
+}
+
+/// Writes the element without the XML declaration, to embed it into another document.
+pub fn write_element(element: &Element) -> crate::Result<String> {
+    let mut out = Vec::new();
+    element.write_with_config(
+        &mut out,
+        xmltree::EmitterConfig::new().write_document_declaration(false),
+    )?;
+    String::from_utf8(out)
+        .ok()
+        .context("element is not valid utf-8")
+}
+
+/// Collects the outermost descendants of `element` with the local name `name`.
+pub fn find_elements<'a>(element: &'a Element, name: &str, out: &mut Vec<&'a Element>) {
+    for child in element.children.iter().filter_map(|x| x.as_element()) {
+        if child.name == name {
+            out.push(child);
+        } else {
+            find_elements(child, name, out);
+        }
+    }
+}
+
+pub fn child<'a>(element: &'a Element, name: &str) -> crate::Result<&'a Element> {
+    element
+        .get_child(name)
+        .with_context(|| format!("{} has no {} element", element.name, name))
+}
+
+pub fn child_text(element: &Element, name: &str) -> crate::Result<String> {
+    Ok(element_text(child(element, name)?))
+}
+
+pub fn element_text(element: &Element) -> String {
+    element
+        .get_text()
+        .map(|x| x.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Parses an RFC 3339 timestamp, like the ones in ACSMs and license tokens.
+pub fn parse_datetime(s: &str) -> crate::Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s.trim())
+        .ok()
+        .map(|x| x.with_timezone(&Utc))
+        .with_context(|| format!("invalid timestamp: {}", s))
+}
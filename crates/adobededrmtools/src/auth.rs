@@ -1,11 +1,16 @@
 use super::{HttpClient, adept, services::AdobeAuthServiceInfo};
 use crate::serializarion::serde_base64;
 use adobededrmtools_crypto::{
-    b64, decrypt_aes, encrypt_aes, encrypt_with_cert, make_keypair, parse_pkcs12, rand_bytes, unb64,
+    Signer, b64, decrypt_aes, encrypt_aes, encrypt_with_cert, make_keypair, parse_pkcs12,
+    rand_bytes, unb64,
 };
-use anyhow::Context;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    AdeptStep, Error,
+    error::{Context, StepContext},
+};
+
 struct EphemeralKey([u8; 16]);
 
 impl EphemeralKey {
@@ -28,18 +33,30 @@ struct SignInCredentials<'a> {
     password: &'a str,
 }
 
-fn serialize_signin_credentials(key: &EphemeralKey, username: &str, password: &str) -> Vec<u8> {
+fn serialize_signin_credentials(
+    key: &EphemeralKey,
+    username: &str,
+    password: &str,
+) -> crate::Result<Vec<u8>> {
     let mut data = Vec::new();
 
     data.extend_from_slice(&key.0);
 
-    data.extend_from_slice(&[username.len() as u8]);
-    data.extend_from_slice(username.as_bytes());
+    append_length_prefixed(&mut data, username).context("username is too long")?;
+    append_length_prefixed(&mut data, password).context("password is too long")?;
+
+    Ok(data)
+}
 
-    data.extend_from_slice(&[password.len() as u8]);
-    data.extend_from_slice(password.as_bytes());
+fn append_length_prefixed(data: &mut Vec<u8>, s: &str) -> crate::Result<()> {
+    // The length is serialized as a single byte.
+    let len = u8::try_from(s.len())
+        .ok()
+        .with_context(|| format!("length must not exceed 255 bytes: {}", s.len()))?;
 
-    data
+    data.push(len);
+    data.extend_from_slice(s.as_bytes());
+    Ok(())
 }
 
 struct PrivateKeys {
@@ -47,15 +64,24 @@ struct PrivateKeys {
     private_license_key: Vec<u8>,
 }
 
+fn encrypt_sign_in_credentials(
+    key: &EphemeralKey,
+    auth_certificate: &[u8],
+    credentials: &SignInCredentials,
+) -> crate::Result<Vec<u8>> {
+    let serialized_credentials =
+        serialize_signin_credentials(key, credentials.username, credentials.password)
+            .context("could not serialize sign in credentials")?;
+    encrypt_with_cert(auth_certificate, &serialized_credentials)
+        .context("could not encrypt authentication credentials for auth certificate")
+}
+
 fn make_sign_in_data(
     key: &EphemeralKey,
     auth_certificate: &[u8],
     credentials: SignInCredentials,
-) -> anyhow::Result<(adept::SignInData, PrivateKeys)> {
-    let serialized_credentials =
-        serialize_signin_credentials(&key, credentials.username, credentials.password);
-    let encrypted_credentials = encrypt_with_cert(&auth_certificate, &serialized_credentials)
-        .context("could not encrypt authentication credentials for auth certificate")?;
+) -> crate::Result<(adept::SignInData, PrivateKeys)> {
+    let encrypted_credentials = encrypt_sign_in_credentials(key, auth_certificate, &credentials)?;
     let (public_auth_key, private_auth_key) = make_keypair();
     let encrypted_private_auth_key = encrypt_aes(key.raw(), &private_auth_key);
     let (public_license_key, private_license_key) = make_keypair();
@@ -78,7 +104,7 @@ fn make_sign_in_data(
 
 fn is_sign_in_method_available(auth_service: &AdobeAuthServiceInfo, method: &str) -> bool {
     let available_methods = &auth_service.sign_in_methods;
-    available_methods.iter().find(|&x| x == method).is_some()
+    available_methods.iter().any(|x| x.method == method)
 }
 
 const ANONYMOUS_CREDENTIALS: SignInCredentials<'static> = SignInCredentials {
@@ -86,42 +112,107 @@ const ANONYMOUS_CREDENTIALS: SignInCredentials<'static> = SignInCredentials {
     password: "",
 };
 
-#[derive(Debug, Clone, Copy)]
-pub enum SignInMethod<'a> {
+/// Method used to sign in to the Adobe authentication service.
+#[derive(Clone, Default)]
+pub enum SignInMethod {
+    /// Anonymous account, not tied to any Adobe ID.
+    #[default]
     Anonymous,
-    _Dummy(&'a str),
+    /// Existing Adobe ID account.
+    AdobeId { username: String, password: String },
+    /// Vendor-specific account, identified by the method advertised by the authentication service.
+    Vendor {
+        method: String,
+        username: String,
+        password: String,
+    },
+}
+
+impl std::fmt::Debug for SignInMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print the password.
+        match self {
+            Self::Anonymous => f.write_str("Anonymous"),
+            Self::AdobeId { username, .. } => f
+                .debug_struct("AdobeId")
+                .field("username", username)
+                .finish_non_exhaustive(),
+            Self::Vendor {
+                method, username, ..
+            } => f
+                .debug_struct("Vendor")
+                .field("method", method)
+                .field("username", username)
+                .finish_non_exhaustive(),
+        }
+    }
 }
 
-fn sign_in_method_to_credentials(
+fn sign_in_method_to_credentials<'a>(
     auth_service: &AdobeAuthServiceInfo,
-    sign_in_method: SignInMethod<'_>,
-) -> anyhow::Result<SignInCredentials<'static>> {
+    sign_in_method: &'a SignInMethod,
+) -> crate::Result<SignInCredentials<'a>> {
     let method = sign_in_method_to_method_name(sign_in_method);
     if !is_sign_in_method_available(auth_service, method) {
-        return Err(anyhow::anyhow!(
-            "sign in method is not available: {}",
-            method
-        ));
+        return Err(Error::Unsupported(format!(
+            "sign in method is not available: {}. available methods: {:?}",
+            method,
+            auth_service
+                .sign_in_methods
+                .iter()
+                .map(|x| &x.method)
+                .collect::<Vec<_>>()
+        )));
     }
 
-    let credentials = match sign_in_method {
-        SignInMethod::Anonymous => ANONYMOUS_CREDENTIALS,
-        SignInMethod::_Dummy(_) => unreachable!(),
-    };
+    Ok(sign_in_method_credentials(sign_in_method))
+}
 
-    Ok(credentials)
+fn sign_in_method_credentials(sign_in_method: &SignInMethod) -> SignInCredentials<'_> {
+    match sign_in_method {
+        SignInMethod::Anonymous => ANONYMOUS_CREDENTIALS,
+        SignInMethod::AdobeId { username, password }
+        | SignInMethod::Vendor {
+            username, password, ..
+        } => SignInCredentials {
+            username: username.as_str(),
+            password: password.as_str(),
+        },
+    }
 }
 
-fn sign_in_method_to_method_name(sign_in_method: SignInMethod<'_>) -> &'static str {
+fn sign_in_method_to_method_name(sign_in_method: &SignInMethod) -> &str {
     match sign_in_method {
         SignInMethod::Anonymous => "anonymous",
-        SignInMethod::_Dummy(_) => unreachable!(),
+        SignInMethod::AdobeId { .. } => "AdobeID",
+        SignInMethod::Vendor { method, .. } => method,
+    }
+}
+
+/// Named account the user is signed in with. Anonymous users don't have one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountUsername {
+    pub method: String,
+    pub username: String,
+}
+
+fn sign_in_method_to_username(sign_in_method: &SignInMethod) -> Option<AccountUsername> {
+    match sign_in_method {
+        SignInMethod::Anonymous => None,
+        SignInMethod::AdobeId { username, .. } | SignInMethod::Vendor { username, .. } => {
+            Some(AccountUsername {
+                method: sign_in_method_to_method_name(sign_in_method).to_string(),
+                username: username.clone(),
+            })
+        }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserCredentials {
     pub user: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<AccountUsername>,
     #[serde(with = "serde_base64")]
     pub private_auth_key: Vec<u8>,
     #[serde(with = "serde_base64")]
@@ -135,8 +226,8 @@ pub struct UserCredentials {
 pub async fn sign_in<H: HttpClient>(
     http_client: &H,
     auth_service: &AdobeAuthServiceInfo,
-    sign_in_method: SignInMethod<'_>,
-) -> anyhow::Result<UserCredentials> {
+    sign_in_method: &SignInMethod,
+) -> crate::Result<UserCredentials> {
     let ephemeral_key = EphemeralKey::generate();
     let sign_in_credentials = sign_in_method_to_credentials(auth_service, sign_in_method)
         .context("could not convert sign in method to credentials")?;
@@ -150,7 +241,7 @@ pub async fn sign_in<H: HttpClient>(
 
     let credentials = adept::sign_in(http_client, &auth_service.auth_url, method, sign_in_data)
         .await
-        .context("sign in failed")?;
+        .step(AdeptStep::SignIn)?;
 
     log::debug!("credentials: {:?}", credentials);
 
@@ -179,9 +270,153 @@ pub async fn sign_in<H: HttpClient>(
 
     Ok(UserCredentials {
         user: credentials.user,
+        username: sign_in_method_to_username(sign_in_method),
         private_auth_key: parsed_pkcs.pkey,
         user_certificate: parsed_pkcs.cert,
         private_license_key,
         license_certificate,
     })
 }
+
+/// Adds a named sign in method to an existing user, e.g. links an anonymous user to an Adobe ID.
+///
+/// Fails without contacting the authentication service if it doesn't offer the method.
+pub async fn add_sign_in<H: HttpClient>(
+    http_client: &H,
+    signer: &Signer,
+    auth_service: &AdobeAuthServiceInfo,
+    user: &str,
+    sign_in_method: &SignInMethod,
+) -> crate::Result<AccountUsername> {
+    let username = sign_in_method_to_username(sign_in_method)
+        .context("cannot add anonymous sign in method to an existing user")?;
+    let sign_in_credentials = sign_in_method_to_credentials(auth_service, sign_in_method)
+        .context("could not convert sign in method to credentials")?;
+
+    // The key is not used to encrypt anything in the response, since the user keeps its keys.
+    let ephemeral_key = EphemeralKey::generate();
+    let encrypted_credentials = encrypt_sign_in_credentials(
+        &ephemeral_key,
+        &auth_service.auth_certificate,
+        &sign_in_credentials,
+    )?;
+
+    adept::add_sign_in(
+        http_client,
+        signer,
+        &auth_service.auth_url,
+        adept::AddSignInData {
+            method: username.method.clone(),
+            sign_in_data: b64(&encrypted_credentials),
+            user: user.to_string(),
+        },
+    )
+    .await
+    .step(AdeptStep::AddSignIn)?;
+
+    Ok(username)
+}
+
+#[cfg(test)]
+mod tests {
+    use adobededrmtools_crypto::make_signer;
+
+    use super::{
+        EphemeralKey, SignInMethod, add_sign_in, serialize_signin_credentials,
+        sign_in_method_to_credentials, sign_in_method_to_username,
+    };
+    use crate::{
+        Error,
+        http_client::testing::MockHttpClient,
+        services::{AdobeAuthServiceInfo, SignInMethodInfo},
+        testing::{USER, USER_KEY},
+    };
+
+    fn auth_service(methods: &[&str]) -> AdobeAuthServiceInfo {
+        AdobeAuthServiceInfo {
+            auth_url: "https://adeactivate.adobe.com/adept".to_string(),
+            auth_certificate: Vec::new(),
+            sign_in_methods: methods
+                .iter()
+                .map(|method| SignInMethodInfo {
+                    method: method.to_string(),
+                    method_type: "standard".to_string(),
+                    name: method.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_serialize_signin_credentials() {
+        let key = EphemeralKey::from_bytes([7; 16]);
+        let data = serialize_signin_credentials(&key, "user@example.com", "secret").unwrap();
+
+        let mut expected = vec![7; 16];
+        expected.push(16);
+        expected.extend_from_slice(b"user@example.com");
+        expected.push(6);
+        expected.extend_from_slice(b"secret");
+        assert_eq!(data, expected);
+
+        let too_long = "a".repeat(256);
+        assert!(serialize_signin_credentials(&key, &too_long, "").is_err());
+        assert!(serialize_signin_credentials(&key, "", &too_long).is_err());
+        assert!(serialize_signin_credentials(&key, &"a".repeat(255), "").is_ok());
+    }
+
+    #[test]
+    fn test_adobe_id_credentials() {
+        let method = SignInMethod::AdobeId {
+            username: "user@example.com".to_string(),
+            password: "secret".to_string(),
+        };
+
+        let credentials =
+            sign_in_method_to_credentials(&auth_service(&["anonymous", "AdobeID"]), &method)
+                .unwrap();
+        assert_eq!(credentials.username, "user@example.com");
+        assert_eq!(credentials.password, "secret");
+
+        let username = sign_in_method_to_username(&method).unwrap();
+        assert_eq!(username.method, "AdobeID");
+        assert_eq!(username.username, "user@example.com");
+        assert!(sign_in_method_to_username(&SignInMethod::Anonymous).is_none());
+
+        let err = sign_in_method_to_credentials(&auth_service(&["anonymous"]), &method)
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::Unsupported(_)));
+
+        // The password must never end up in logs.
+        assert!(!format!("{method:?}").contains("secret"));
+    }
+
+    #[tokio::test]
+    async fn test_add_sign_in_unavailable_method() {
+        let http = MockHttpClient::new();
+        let signer = make_signer(USER_KEY).unwrap();
+        let method = SignInMethod::AdobeId {
+            username: "user@example.com".to_string(),
+            password: "secret".to_string(),
+        };
+
+        // The authentication service is only asked to add methods it offers.
+        let err = add_sign_in(&http, &signer, &auth_service(&["anonymous"]), USER, &method)
+            .await
+            .unwrap_err();
+        assert!(matches!(err.root(), Error::Unsupported(_)));
+
+        let err = add_sign_in(
+            &http,
+            &signer,
+            &auth_service(&["anonymous", "AdobeID"]),
+            USER,
+            &SignInMethod::Anonymous,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err.root(), Error::InvalidData(_)));
+        assert!(http.requests().is_empty());
+    }
+}
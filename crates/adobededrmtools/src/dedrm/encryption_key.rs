@@ -1,5 +1,7 @@
 use adobededrmtools_crypto::Pkey;
 
+use crate::Error;
+
 /// Adobe DRM-protected file content encryption key.
 /// Obtain from [`decrypt_adept_encryption_key`].
 #[derive(Clone, Copy)]
@@ -18,16 +20,16 @@ impl AdeptEncryptionKey {
 pub fn decrypt_adept_encryption_key(
     encrypted_key: &[u8],
     private_license_key: &[u8],
-) -> anyhow::Result<AdeptEncryptionKey> {
+) -> crate::Result<AdeptEncryptionKey> {
     let pkey = Pkey::from_der(private_license_key)?;
     let mut decrypted_key: [u8; 16] = [0; 16];
     let decrypted = pkey.decrypt(encrypted_key)?;
 
     if decrypted.len() != 16 {
-        return Err(anyhow::anyhow!(
+        return Err(Error::InvalidData(format!(
             "decrypted key length is different from 16: {}",
             decrypted.len()
-        ));
+        )));
     }
 
     decrypted_key.copy_from_slice(&decrypted);
@@ -1,5 +1,7 @@
 use serde::Deserialize;
 
+use crate::Error;
+
 // Example of META-INF/encryption.xml file contents:
 // <?xml version="1.0" encoding="UTF-8" standalone="no"?><encryption xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
 //   <EncryptedData xmlns="http://www.w3.org/2001/04/xmlenc#">
@@ -67,9 +69,9 @@ struct CipherReference {
     uri: String,
 }
 
-pub fn parse_encryption_file(s: &str) -> anyhow::Result<Vec<EncryptedData>> {
+pub fn parse_encryption_file(s: &str) -> crate::Result<Vec<EncryptedData>> {
     let encryption: Encryption = quick_xml::de::from_str(s)?;
-    Ok(encryption
+    encryption
         .encrypted_data
         .into_iter()
         .map(|d| {
@@ -83,10 +85,10 @@ pub fn parse_encryption_file(s: &str) -> anyhow::Result<Vec<EncryptedData>> {
                     compression: CompressionAlgorithm::None,
                 },
                 _ => {
-                    return Err(anyhow::anyhow!(
-                        "unsupported encryption algorithm: {}",
+                    return Err(Error::Unsupported(format!(
+                        "encryption algorithm: {}",
                         d.encryption_method.algorithm
-                    ));
+                    )));
                 }
             };
 
@@ -95,5 +97,5 @@ pub fn parse_encryption_file(s: &str) -> anyhow::Result<Vec<EncryptedData>> {
                 algorithm,
             })
         })
-        .collect::<Result<Vec<_>, _>>()?)
+        .collect::<Result<Vec<_>, _>>()
 }
@@ -8,7 +8,6 @@ use std::{
 
 use adobededrmtools_crypto::decrypt_aes;
 
-use anyhow::Context;
 use encryption_file::{
     Algorithm, CompressionAlgorithm, EncryptionAlgorithm, parse_encryption_file,
 };
@@ -16,12 +15,13 @@ use encryption_file::{
 use zip_rebuilder::{ZipFileDisposition, ZipFileW, ZipReader, ZipRebuilder, rebuild_zip};
 
 use super::AdeptEncryptionKey;
+use crate::error::Context;
 
 fn decrypt_file(
     encryption_key: &AdeptEncryptionKey,
     data: &[u8],
     algorithm: &EncryptionAlgorithm,
-) -> anyhow::Result<Vec<u8>> {
+) -> crate::Result<Vec<u8>> {
     match algorithm {
         EncryptionAlgorithm::Aes128Cbc => {
             Ok(decrypt_aes(&encryption_key.raw(), data).context("failed to decrypt aes128cbc")?)
@@ -29,7 +29,7 @@ fn decrypt_file(
     }
 }
 
-fn decompress_file(data: &[u8], algorithm: &CompressionAlgorithm) -> anyhow::Result<Vec<u8>> {
+fn decompress_file(data: &[u8], algorithm: &CompressionAlgorithm) -> crate::Result<Vec<u8>> {
     match algorithm {
         CompressionAlgorithm::None => Ok(data.to_vec()),
         CompressionAlgorithm::Deflate => {
@@ -45,7 +45,7 @@ fn decode_file(
     encryption_key: &AdeptEncryptionKey,
     data: &[u8],
     algorithm: &Algorithm,
-) -> anyhow::Result<Vec<u8>> {
+) -> crate::Result<Vec<u8>> {
     let decrypted = decrypt_file(encryption_key, data, &algorithm.encryption)
         .context("failed to decrypt file")?;
     let decompressed =
@@ -67,7 +67,7 @@ impl EpubDecryptRebuilder {
 }
 
 impl ZipRebuilder for EpubDecryptRebuilder {
-    fn init<Z: ZipReader>(&mut self, zip: &mut Z) -> anyhow::Result<()> {
+    fn init<Z: ZipReader>(&mut self, zip: &mut Z) -> crate::Result<()> {
         let Some(encryption_file_contents) = zip.read_file(Self::ENCRYPTION_FILEPATH)? else {
             log::warn!(
                 "No META-INF/encryption.xml file in the EPUB archive. Not decrypting anything. Maybe the EPUB is DRM-free?"
@@ -88,7 +88,7 @@ impl ZipRebuilder for EpubDecryptRebuilder {
         Ok(())
     }
 
-    fn process_file<Z: ZipFileW>(&mut self, file: &mut Z) -> anyhow::Result<ZipFileDisposition> {
+    fn process_file<Z: ZipFileW>(&mut self, file: &mut Z) -> crate::Result<ZipFileDisposition> {
         if self.should_ship_file(file.name()) {
             log::debug!("skipping file: {}", file.name());
             return Ok(ZipFileDisposition::Delete);
@@ -115,7 +115,7 @@ pub fn dedrm_epub<R: Read + Seek, W: Write + Seek>(
     input: R,
     output: W,
     encryption_key: AdeptEncryptionKey,
-) -> anyhow::Result<W> {
+) -> crate::Result<W> {
     rebuild_zip(
         input,
         output,
@@ -1,12 +1,13 @@
 use std::io::{Read, Seek, Write};
 
-use anyhow::Context;
 use zip::{ZipArchive, read::ZipFile};
 
+use crate::error::Context;
+
 pub trait ZipRebuilder {
-    fn init<Z: ZipReader>(&mut self, zip: &mut Z) -> anyhow::Result<()>;
+    fn init<Z: ZipReader>(&mut self, zip: &mut Z) -> crate::Result<()>;
 
-    fn process_file<Z: ZipFileW>(&mut self, file: &mut Z) -> anyhow::Result<ZipFileDisposition>;
+    fn process_file<Z: ZipFileW>(&mut self, file: &mut Z) -> crate::Result<ZipFileDisposition>;
 }
 
 pub enum ZipFileDisposition {
@@ -18,11 +19,11 @@ pub enum ZipFileDisposition {
 pub trait ZipFileW {
     fn name(&self) -> &str;
 
-    fn read_file(&mut self) -> anyhow::Result<Vec<u8>>;
+    fn read_file(&mut self) -> crate::Result<Vec<u8>>;
 }
 
 pub trait ZipReader {
-    fn read_file(&mut self, name: &str) -> anyhow::Result<Option<Vec<u8>>>;
+    fn read_file(&mut self, name: &str) -> crate::Result<Option<Vec<u8>>>;
 }
 
 impl<'a, R: Read + Seek> ZipFileW for ZipFile<'a, R> {
@@ -30,7 +31,7 @@ impl<'a, R: Read + Seek> ZipFileW for ZipFile<'a, R> {
         self.name()
     }
 
-    fn read_file(&mut self) -> anyhow::Result<Vec<u8>> {
+    fn read_file(&mut self) -> crate::Result<Vec<u8>> {
         let mut buf = Vec::new();
         self.read_to_end(&mut buf)?;
         Ok(buf)
@@ -38,7 +39,7 @@ impl<'a, R: Read + Seek> ZipFileW for ZipFile<'a, R> {
 }
 
 impl<R: Read + Seek> ZipReader for ZipArchive<R> {
-    fn read_file(&mut self, name: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    fn read_file(&mut self, name: &str) -> crate::Result<Option<Vec<u8>>> {
         match self.by_name(name) {
             Ok(mut file) => {
                 let mut buf = Vec::new();
@@ -51,7 +52,7 @@ impl<R: Read + Seek> ZipReader for ZipArchive<R> {
     }
 }
 
-pub fn rebuild_zip<R, W, B>(input: R, output: W, mut rebuilder: B) -> anyhow::Result<W>
+pub fn rebuild_zip<R, W, B>(input: R, output: W, mut rebuilder: B) -> crate::Result<W>
 where
     R: Read + Seek,
     W: Write + Seek,
@@ -87,5 +88,5 @@ where
         }
     }
 
-    Ok(out_arhive.finish().context("zip finish failed")?)
+    out_arhive.finish().context("zip finish failed")
 }
@@ -0,0 +1,116 @@
+use adobededrmtools_crypto::Pkey;
+
+use super::{AdeptEncryptionKey, decrypt_adept_encryption_key};
+use crate::{Error, error::Context};
+
+/// Converts a private license key into the `adobekey.der` format used by DeDRM_tools,
+/// i.e. a PKCS#1 RSA private key.
+pub fn export_adobe_key(private_license_key: &[u8]) -> crate::Result<Vec<u8>> {
+    Pkey::from_der(private_license_key)
+        .context("invalid private license key")?
+        .to_pkcs1_der()
+        .context("could not encode private license key")
+}
+
+/// Decrypt-only set of private license keys.
+///
+/// Doesn't need an account, so resources can be decrypted offline with keys obtained elsewhere,
+/// e.g. `adobekey.der` files exported by DeDRM_tools.
+#[derive(Clone, Default)]
+pub struct KeyStore {
+    /// PKCS#8 private license keys.
+    keys: Vec<Vec<u8>>,
+}
+
+impl KeyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_adobe_key(adobe_key: &[u8]) -> crate::Result<Self> {
+        let mut key_store = Self::new();
+        key_store.add_adobe_key(adobe_key)?;
+        Ok(key_store)
+    }
+
+    /// Adds a PKCS#8 private license key, as stored in [`UserCredentials`](crate::UserCredentials).
+    pub fn add_private_license_key(&mut self, private_license_key: &[u8]) -> crate::Result<()> {
+        Pkey::from_der(private_license_key).context("invalid private license key")?;
+        self.keys.push(private_license_key.to_vec());
+        Ok(())
+    }
+
+    /// Adds a key from an `adobekey.der` file. Both PKCS#1 and PKCS#8 keys are accepted.
+    pub fn add_adobe_key(&mut self, adobe_key: &[u8]) -> crate::Result<()> {
+        let pkey = Pkey::from_pkcs1_der(adobe_key)
+            .or_else(|_| Pkey::from_der(adobe_key))
+            .context("adobe key is neither a PKCS#1 nor a PKCS#8 RSA private key")?;
+        self.keys.push(pkey.to_pkcs8_der()?);
+        Ok(())
+    }
+
+    pub fn private_license_keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.keys.iter().map(|x| x.as_slice())
+    }
+
+    /// Decrypts the resource encryption key with the first key that fits.
+    pub fn decrypt_encryption_key(
+        &self,
+        encrypted_key: &[u8],
+    ) -> crate::Result<AdeptEncryptionKey> {
+        let mut last_err = Error::InvalidData("key store is empty".to_string());
+        for key in self.private_license_keys() {
+            match decrypt_adept_encryption_key(encrypted_key, key) {
+                Ok(encryption_key) => return Ok(encryption_key),
+                Err(err) => last_err = err,
+            }
+        }
+
+        Err(last_err).context("none of the keys could decrypt the adept encryption key")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use adobededrmtools_crypto::make_keypair;
+
+    use super::{KeyStore, export_adobe_key};
+    use crate::Error;
+
+    #[test]
+    fn test_adobe_key_round_trip() {
+        crate::init_test_rand();
+        let (_, private_license_key) = make_keypair();
+
+        let adobe_key = export_adobe_key(&private_license_key).expect("export_adobe_key failed");
+        // DeDRM_tools strips the 26 bytes long PKCS#8 header.
+        assert_eq!(adobe_key, private_license_key[26..]);
+
+        let key_store = KeyStore::from_adobe_key(&adobe_key).expect("from_adobe_key failed");
+        assert_eq!(
+            key_store.private_license_keys().collect::<Vec<_>>(),
+            vec![private_license_key.as_slice()]
+        );
+    }
+
+    #[test]
+    fn test_decrypt_with_wrong_key() {
+        crate::init_test_rand();
+        let (_, private_license_key) = make_keypair();
+
+        let err = KeyStore::new()
+            .decrypt_encryption_key(&[0; 128])
+            .err()
+            .unwrap();
+        assert!(matches!(err.root(), Error::InvalidData(_)));
+
+        // The error of the last key is kept, rather than a bare "not found".
+        let mut key_store = KeyStore::new();
+        key_store
+            .add_private_license_key(&private_license_key)
+            .unwrap();
+        let err = key_store.decrypt_encryption_key(&[0; 128]).err().unwrap();
+        assert!(matches!(err, Error::Context { .. }));
+        assert!(!matches!(err.root(), Error::InvalidData(_)));
+    }
+}
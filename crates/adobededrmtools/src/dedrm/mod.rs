@@ -1,11 +1,13 @@
 use std::io::Cursor;
 
-use anyhow::Context;
-
 mod encryption_key;
 pub mod epub;
+mod key_store;
 
 pub use encryption_key::{AdeptEncryptionKey, decrypt_adept_encryption_key};
+pub use key_store::{KeyStore, export_adobe_key};
+
+use crate::error::Context;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ResourceType {
@@ -33,10 +35,32 @@ pub fn dedrm_resource(
     encrypted_key: &[u8],
     private_license_key: &[u8],
     encrypted_resource: &[u8],
-) -> anyhow::Result<Vec<u8>> {
+) -> crate::Result<Vec<u8>> {
     let encryption_key = decrypt_adept_encryption_key(encrypted_key, private_license_key)
         .context("could not decrypt adept encryption key")?;
 
+    dedrm_resource_with_encryption_key(resource_type, encryption_key, encrypted_resource)
+}
+
+/// Same as [`dedrm_resource`], but tries every key of the key store.
+pub fn dedrm_resource_with_key_store(
+    resource_type: ResourceType,
+    encrypted_key: &[u8],
+    key_store: &KeyStore,
+    encrypted_resource: &[u8],
+) -> crate::Result<Vec<u8>> {
+    let encryption_key = key_store
+        .decrypt_encryption_key(encrypted_key)
+        .context("could not decrypt adept encryption key")?;
+
+    dedrm_resource_with_encryption_key(resource_type, encryption_key, encrypted_resource)
+}
+
+fn dedrm_resource_with_encryption_key(
+    resource_type: ResourceType,
+    encryption_key: AdeptEncryptionKey,
+    encrypted_resource: &[u8],
+) -> crate::Result<Vec<u8>> {
     match resource_type {
         ResourceType::Epub => Ok(dedrm_epub_resource(encryption_key, encrypted_resource)?),
     }
@@ -45,7 +69,7 @@ pub fn dedrm_resource(
 pub fn dedrm_epub_resource(
     encryption_key: AdeptEncryptionKey,
     encrypted_resource: &[u8],
-) -> anyhow::Result<Vec<u8>> {
+) -> crate::Result<Vec<u8>> {
     let decrypted_resource = epub::dedrm_epub(
         Cursor::new(encrypted_resource),
         Cursor::new(Vec::new()),
@@ -0,0 +1,292 @@
+use std::{error::Error as StdError, fmt::Display};
+
+use chrono::{DateTime, Utc};
+
+use crate::{AdeptError, AdeptErrorCode, adept::http_client::HttpError};
+
+/// Error returned by the library.
+///
+/// Errors are usually wrapped in [`Error::Context`] and [`Error::Step`] on their way up, use
+/// [`Error::root`] to get the underlying cause.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The [`HttpClient`](crate::HttpClient) failed to perform a request.
+    #[error("transport error: {}", ErrorChain(.0.as_ref()))]
+    Transport(HttpError),
+    /// The ADEPT service responded with an error.
+    #[error(transparent)]
+    Adept(#[from] AdeptError),
+    /// The service response is not a valid ADEPT response.
+    #[error("protocol error: {0}")]
+    Protocol(String),
+    #[error("xml error: {0}")]
+    Xml(Box<dyn StdError + Send + Sync>),
+    #[error(transparent)]
+    Crypto(#[from] adobededrmtools_crypto::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("zip error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The input is malformed or lacks required data.
+    #[error("{0}")]
+    InvalidData(String),
+    /// The ACSM can't be fulfilled anymore.
+    #[error("acsm expired at {expiration}")]
+    AcsmExpired { expiration: DateTime<Utc> },
+    /// A signed element doesn't match its signature.
+    #[error("signature mismatch: {0}")]
+    SignatureMismatch(String),
+    /// The data is not available without touching the network, in offline mode.
+    #[error("offline: {0}")]
+    Offline(String),
+    /// The input uses a format or an algorithm that is not supported.
+    #[error("unsupported: {0}")]
+    Unsupported(String),
+    /// The user has activated the maximum number of devices.
+    #[error("activation limit reached, {guidance}: {source}")]
+    ActivationLimit {
+        guidance: String,
+        source: Box<Error>,
+    },
+    #[error("{step} failed: {source}")]
+    Step { step: AdeptStep, source: Box<Error> },
+    #[error("{context}: {source}")]
+    Context { context: String, source: Box<Error> },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// The underlying error, without the context it was wrapped in.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Step { source, .. }
+            | Error::Context { source, .. }
+            | Error::ActivationLimit { source, .. } => source.root(),
+            err => err,
+        }
+    }
+
+    /// The innermost ADEPT step the error happened in.
+    pub fn step(&self) -> Option<AdeptStep> {
+        match self {
+            Error::Step { step, source } => source.step().or(Some(*step)),
+            Error::Context { source, .. } | Error::ActivationLimit { source, .. } => source.step(),
+            _ => None,
+        }
+    }
+
+    /// The error the ADEPT service responded with.
+    pub fn adept_error(&self) -> Option<&AdeptError> {
+        match self.root() {
+            Error::Adept(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// Whether the user has activated the maximum number of devices.
+    pub fn is_activation_limit(&self) -> bool {
+        self.adept_error()
+            .is_some_and(|x| x.code == AdeptErrorCode::TooManyActivations)
+    }
+}
+
+/// Prints an error followed by its sources, as HTTP client errors tend to keep the useful part
+/// of the message in their sources.
+struct ErrorChain<'a>(&'a (dyn StdError + 'static));
+
+impl Display for ErrorChain<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)?;
+        let mut source = self.0.source();
+        while let Some(err) = source {
+            write!(f, ": {}", err)?;
+            source = err.source();
+        }
+        Ok(())
+    }
+}
+
+/// Only [`HttpClient`](crate::HttpClient) implementations return boxed errors.
+impl From<HttpError> for Error {
+    fn from(err: HttpError) -> Self {
+        Error::Transport(err)
+    }
+}
+
+impl From<quick_xml::DeError> for Error {
+    fn from(err: quick_xml::DeError) -> Self {
+        Error::Xml(Box::new(err))
+    }
+}
+
+impl From<quick_xml::SeError> for Error {
+    fn from(err: quick_xml::SeError) -> Self {
+        Error::Xml(Box::new(err))
+    }
+}
+
+impl From<xmltree::ParseError> for Error {
+    fn from(err: xmltree::ParseError) -> Self {
+        Error::Xml(Box::new(err))
+    }
+}
+
+impl From<xmltree::Error> for Error {
+    fn from(err: xmltree::Error) -> Self {
+        Error::Xml(Box::new(err))
+    }
+}
+
+/// ADEPT request made by the library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdeptStep {
+    ActivationServiceInfo,
+    AuthenticationServiceInfo,
+    SignIn,
+    AddSignIn,
+    Activate,
+    Deactivate,
+    FulfillmentAuth,
+    InitLicenseService,
+    Fulfill,
+    DownloadAgain,
+    LicenseServiceInfo,
+    Notify,
+    ReturnLoan,
+    UserInfo,
+}
+
+impl Display for AdeptStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AdeptStep::ActivationServiceInfo => "activation service info",
+            AdeptStep::AuthenticationServiceInfo => "authentication service info",
+            AdeptStep::SignIn => "sign in",
+            AdeptStep::AddSignIn => "add sign in",
+            AdeptStep::Activate => "activate",
+            AdeptStep::Deactivate => "deactivate",
+            AdeptStep::FulfillmentAuth => "fulfillment auth",
+            AdeptStep::InitLicenseService => "init license service",
+            AdeptStep::Fulfill => "fulfill",
+            AdeptStep::DownloadAgain => "download again",
+            AdeptStep::LicenseServiceInfo => "license service info",
+            AdeptStep::Notify => "notify",
+            AdeptStep::ReturnLoan => "loan return",
+            AdeptStep::UserInfo => "user info",
+        })
+    }
+}
+
+/// `anyhow`-like context for results and options.
+pub(crate) trait Context<T> {
+    fn context<C: Display>(self, context: C) -> Result<T>;
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, f: F) -> Result<T>;
+}
+
+pub(crate) trait StepContext<T> {
+    /// Marks the error as coming from the ADEPT `step`.
+    fn step(self, step: AdeptStep) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn context<C: Display>(self, context: C) -> Result<T> {
+        self.map_err(|err| Error::Context {
+            context: context.to_string(),
+            source: Box::new(err.into()),
+        })
+    }
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.map_err(|err| Error::Context {
+            context: f().to_string(),
+            source: Box::new(err.into()),
+        })
+    }
+}
+
+impl<T, E: Into<Error>> StepContext<T> for std::result::Result<T, E> {
+    fn step(self, step: AdeptStep) -> Result<T> {
+        self.map_err(|err| Error::Step {
+            step,
+            source: Box::new(err.into()),
+        })
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context<C: Display>(self, context: C) -> Result<T> {
+        self.ok_or_else(|| Error::InvalidData(context.to_string()))
+    }
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.ok_or_else(|| Error::InvalidData(f().to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AdeptStep, Context, Error, StepContext};
+    use crate::{AdeptError, AdeptErrorCode};
+
+    #[test]
+    fn test_error_context() {
+        let result: Result<(), _> = Err(AdeptError::new(
+            "E_ACT_TOO_MANY_ACTIVATIONS".to_string(),
+            Vec::new(),
+        ));
+        let err = result
+            .step(AdeptStep::Activate)
+            .context("activate_device failed")
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "activate_device failed: activate failed: AdeptError(E_ACT_TOO_MANY_ACTIVATIONS, [])"
+        );
+        assert_eq!(err.step(), Some(AdeptStep::Activate));
+        assert!(err.is_activation_limit());
+        assert_eq!(
+            err.adept_error().map(|x| &x.code),
+            Some(&AdeptErrorCode::TooManyActivations)
+        );
+        assert!(matches!(err.root(), Error::Adept(_)));
+    }
+
+    #[test]
+    fn test_error_cause_printed_once() {
+        use std::error::Error as _;
+
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused");
+        let err = Error::from(Box::new(io) as crate::http_client::HttpError);
+        assert_eq!(err.to_string(), "transport error: refused");
+        assert!(err.source().is_none());
+
+        // The sources of the transport error are printed too.
+        #[derive(Debug, thiserror::Error)]
+        #[error("request failed")]
+        struct RequestError(#[source] std::io::Error);
+
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused");
+        let chained = Error::from(Box::new(RequestError(io)) as crate::http_client::HttpError);
+        assert_eq!(
+            chained.to_string(),
+            "transport error: request failed: refused"
+        );
+
+        let Error::Transport(transport) = &err else {
+            panic!("expected a transport error: {err:?}");
+        };
+        assert_eq!(
+            transport.downcast_ref::<std::io::Error>().map(|x| x.kind()),
+            Some(std::io::ErrorKind::ConnectionRefused)
+        );
+
+        let err = Error::from(xmltree::Element::parse(&b"<a>"[..]).unwrap_err());
+        assert!(err.to_string().starts_with("xml error: "));
+        assert!(err.source().is_none());
+    }
+}
@@ -1,64 +1,118 @@
-use anyhow::Context;
-use serde::{Deserialize, Serialize};
-
-use crate::UserCredentials;
-use crate::serializarion::serde_base64;
+use std::{path::Path, sync::Arc, time::Duration};
 
 use super::{
-    Acsm, DEFAULT_ACTIVATION_URL, HttpClient,
-    activation::{DeviceInfo, activate_device},
-    auth::{SignInMethod, sign_in},
-    fulfillment::{Resource, fulfill, fulfillment_auth, init_license_service},
+    AccountArchive, AccountDevice, Acsm, ActivationLimitPolicy, AdeptStep, AdobeAccount,
+    AdobeMinServicesInfo, Clock, DEFAULT_ACTIVATION_URL, HttpClient, SystemClock,
+    activation::{DeviceInfo, activate_device, activate_target_device, deactivate_device},
+    activation_limit::{
+        LimitRecovery, activation_limit_error, recover_activation_limit,
+        recover_from_activation_limit,
+    },
+    ade::{DeviceIdentity, SecondaryDevice, SecondaryDeviceActivation, write_account_activation},
+    auth::{SignInMethod, add_sign_in, sign_in},
+    fulfillment::{
+        Loan, Resource, SignaturePolicy, download_again, fulfill, return_loan as inner_return_loan,
+    },
     make_signer,
-    services::get_services_info,
+    operator_auth::with_operator_auth,
+    services::{ServicesInfoParams, SignInMethodInfo, get_cached_services_info},
+    user_info::{UserInfo, fetch_user_info},
 };
+use crate::{Error, error::Context};
+
+/// Name of the devices generated for new accounts, as written to `device.xml`.
+const DEVICE_NAME: &str = "adobededrmtools";
 
 pub struct CreateAccountParams {
     pub activation_url: String,
     pub device_info: DeviceInfo,
+    /// Identity the fingerprint of `device_info` is derived from, needed to export the device.
+    pub device_identity: Option<DeviceIdentity>,
+    pub sign_in_method: SignInMethod,
+    pub services_info: ServicesInfoParams,
+    pub activation_limit_policy: ActivationLimitPolicy,
 }
 
 impl Default for CreateAccountParams {
     fn default() -> Self {
+        let identity = DeviceIdentity::generate(DEVICE_NAME);
+
         Self {
             activation_url: DEFAULT_ACTIVATION_URL.to_string(),
-            device_info: DeviceInfo::generate(),
+            device_info: identity.device_info(),
+            device_identity: Some(identity),
+            sign_in_method: SignInMethod::Anonymous,
+            services_info: ServicesInfoParams::default(),
+            activation_limit_policy: ActivationLimitPolicy::default(),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AdobeMinServicesInfo {
-    pub activation_url: String,
-    pub auth_url: String,
-    #[serde(with = "serde_base64")]
-    pub auth_certificate: Vec<u8>,
+const DEFAULT_OPERATOR_AUTH_TTL: Duration = Duration::from_secs(60 * 60 * 24);
+
+pub struct FulfillParams {
+    /// Clock the ACSM expiration is checked against.
+    pub clock: Arc<dyn Clock>,
+    pub license_signature_policy: SignaturePolicy,
+    /// How long the operator authentication recorded in the account is reused for.
+    pub operator_auth_ttl: Duration,
+    /// Id of the account's device to act as. If not set, the first device is used, except when
+    /// downloading a resource again, which uses the device the resource was fulfilled as.
+    pub device: Option<String>,
+    /// What to do when the user has activated the maximum number of devices. Unless it is
+    /// [`ActivationLimitPolicy::Stop`], the account may be updated or replaced while fulfilling.
+    pub activation_limit_policy: ActivationLimitPolicy,
+    /// Where the account is kept before [`ActivationLimitPolicy::RotateAnonymous`] replaces it.
+    pub account_archive: Option<Arc<dyn AccountArchive>>,
+    /// How the services info is looked up when switching to a new account, and the certificates
+    /// the license tokens are checked against.
+    pub services_info: ServicesInfoParams,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AdobeAccount {
-    pub services: AdobeMinServicesInfo,
-    pub user_credentials: UserCredentials,
-    pub device_info: DeviceInfo,
-    pub activated_device: String,
+impl Default for FulfillParams {
+    fn default() -> Self {
+        Self {
+            clock: Arc::new(SystemClock),
+            license_signature_policy: SignaturePolicy::default(),
+            operator_auth_ttl: DEFAULT_OPERATOR_AUTH_TTL,
+            device: None,
+            activation_limit_policy: ActivationLimitPolicy::default(),
+            account_archive: None,
+            services_info: ServicesInfoParams::default(),
+        }
+    }
 }
 
-pub async fn create_adobe_account<H: HttpClient>(
+/// Lists the sign in methods advertised by the authentication service.
+pub async fn get_sign_in_methods<H: HttpClient>(
     http_client: &H,
-    params: CreateAccountParams,
-) -> anyhow::Result<AdobeAccount> {
-    let services = get_services_info(http_client, &params.activation_url)
+    activation_url: &str,
+    services_info: &ServicesInfoParams,
+) -> crate::Result<Vec<SignInMethodInfo>> {
+    let services = get_cached_services_info(http_client, activation_url, services_info)
         .await
         .context("get_services_info failed")?;
 
-    let user_credentials = sign_in(http_client, &services.auth_service, SignInMethod::Anonymous)
+    Ok(services.auth_service.sign_in_methods)
+}
+
+pub async fn create_adobe_account<H: HttpClient>(
+    http_client: &H,
+    params: CreateAccountParams,
+) -> crate::Result<AdobeAccount> {
+    let services =
+        get_cached_services_info(http_client, &params.activation_url, &params.services_info)
+            .await
+            .context("get_services_info failed")?;
+
+    let user_credentials = sign_in(http_client, &services.auth_service, &params.sign_in_method)
         .await
         .context("sign_in failed")?;
 
     let signer = make_signer(&user_credentials.private_auth_key)?;
     let device_info = params.device_info;
 
-    let activated_device = activate_device(
+    let result = activate_device(
         http_client,
         &signer,
         &services.activation_url,
@@ -66,54 +120,874 @@ pub async fn create_adobe_account<H: HttpClient>(
         &device_info,
     )
     .await
-    .context("activate_device failed")?;
+    .context("activate_device failed");
+
+    let device = match result {
+        Ok(activated_device) => AccountDevice {
+            device_info,
+            activation_token: activated_device,
+            identity: params.device_identity,
+        },
+        Err(err) => match recover_activation_limit(
+            http_client,
+            &signer,
+            &services.activation_url,
+            &user_credentials.user,
+            &params.activation_limit_policy,
+            err,
+        )
+        .await?
+        {
+            LimitRecovery::Reused(device) => *device,
+            // The account asked for would be replaced by one of another user.
+            LimitRecovery::Rotate(err) => {
+                return Err(activation_limit_error(err))
+                    .context("cannot switch to a new anonymous user when creating an account");
+            }
+        },
+    };
 
     Ok(AdobeAccount {
         services: AdobeMinServicesInfo {
             activation_url: services.activation_url,
             auth_url: services.auth_service.auth_url,
             auth_certificate: services.auth_service.auth_certificate,
+            user_info_url: Some(services.user_info_url),
         },
         user_credentials,
-        device_info,
-        activated_device: activated_device.device,
+        devices: vec![device],
+        authorized_operators: Vec::new(),
     })
 }
 
-pub async fn fulfill_acsm<H: HttpClient>(
+/// Activates another device the account can fulfill as, e.g. to fulfill as a device of a
+/// different type. The account is updated in place and should be stored again.
+///
+/// `identity` is what the fingerprint of `device_info` is derived from, see
+/// [`DeviceIdentity::device_info`]. Without it, the device can't be exported.
+///
+/// Returns the id of the activated device.
+pub async fn add_account_device<H: HttpClient>(
+    http_client: &H,
+    account: &mut AdobeAccount,
+    device_info: DeviceInfo,
+    identity: Option<DeviceIdentity>,
+) -> crate::Result<String> {
+    let signer = make_signer(&account.user_credentials.private_auth_key)?;
+
+    let activated_device = activate_device(
+        http_client,
+        &signer,
+        &account.services.activation_url,
+        &account.user_credentials.user,
+        &device_info,
+    )
+    .await
+    .context("activate_device failed")?;
+
+    let id = activated_device.device.clone();
+    account.set_device(
+        AccountDevice {
+            device_info,
+            activation_token: activated_device,
+            identity,
+        },
+        false,
+    );
+    Ok(id)
+}
+
+/// Deactivates one of the account's devices, releasing its activation slot. The device is picked
+/// by its id, the first device is deactivated if `device` is `None`. To release every slot the
+/// account holds, deactivate each of its [`devices`](AdobeAccount::devices).
+///
+/// The account cannot be used to fulfill ACSMs as the device afterwards.
+pub async fn deactivate_adobe_account<H: HttpClient>(
     http_client: &H,
-    acsm: &Acsm,
     account: &AdobeAccount,
-) -> anyhow::Result<Vec<Resource>> {
-    fulfillment_auth(
+    device: Option<&str>,
+) -> crate::Result<()> {
+    let signer = make_signer(&account.user_credentials.private_auth_key)?;
+    let device = account.device(device)?;
+
+    deactivate_device(
         http_client,
-        acsm,
-        &account.user_credentials,
-        &account.services.auth_certificate,
+        &signer,
+        &account.services.activation_url,
+        &account.user_credentials.user,
+        &device.device_info,
+        device.id(),
     )
-    .await?;
+    .await
+    .context("deactivate_device failed")?;
+
+    Ok(())
+}
+
+/// Activates an extra reading device under the account's user.
+///
+/// The activation records of the device can then be written with
+/// [`write_device_activation`](crate::write_device_activation).
+pub async fn activate_secondary_device<H: HttpClient>(
+    http_client: &H,
+    account: &AdobeAccount,
+    device: &SecondaryDevice,
+    services_info: &ServicesInfoParams,
+) -> crate::Result<SecondaryDeviceActivation> {
+    let services =
+        get_cached_services_info(http_client, &account.services.activation_url, services_info)
+            .await
+            .context("get_services_info failed")?;
 
     let signer = make_signer(&account.user_credentials.private_auth_key)?;
 
-    init_license_service(
+    let activated_device = activate_target_device(
         http_client,
         &signer,
         &account.services.activation_url,
         &account.user_credentials.user,
+        &account.device(None)?.device_info,
+        &device.device_info,
+    )
+    .await
+    .context("activate_target_device failed")?;
+
+    Ok(SecondaryDeviceActivation {
+        activated_device,
+        user_info_url: services.user_info_url,
+        activation_certificate: services.activation_certificate,
+    })
+}
+
+/// Writes the account as `activation.xml`, `device.xml` and `devicesalt` into `dir`, in the layout
+/// used by libgourou and ADE. Only the device picked by `device` is written, see
+/// [`AdobeAccount::device`]. The key material is protected with the key of the device's
+/// [`identity`](AccountDevice::identity).
+///
+/// Fails for devices without a stored activation token or identity, i.e. created before they
+/// were stored or activated with a given fingerprint.
+pub async fn export_adobe_account<H: HttpClient>(
+    http_client: &H,
+    account: &AdobeAccount,
+    device: Option<&str>,
+    dir: &Path,
+    services_info: &ServicesInfoParams,
+) -> crate::Result<()> {
+    // The activation certificate is not stored in the account.
+    let services =
+        get_cached_services_info(http_client, &account.services.activation_url, services_info)
+            .await
+            .context("get_services_info failed")?;
+
+    write_account_activation(
+        dir,
+        account,
+        device,
+        &services.user_info_url,
+        &services.activation_certificate,
+    )
+}
+
+/// Links an anonymous account to an Adobe ID.
+///
+/// The account keeps its user and keys, so the resources fulfilled with it earlier stay usable.
+/// On success, the account is updated in place and should be stored again. Fails if the
+/// authentication service of the account, looked up with `services_info`, doesn't offer Adobe ID.
+pub async fn link_adobe_id<H: HttpClient>(
+    http_client: &H,
+    account: &mut AdobeAccount,
+    username: &str,
+    password: &str,
+    services_info: &ServicesInfoParams,
+) -> crate::Result<()> {
+    if let Some(existing) = &account.user_credentials.username {
+        return Err(Error::InvalidData(format!(
+            "account is already linked to {} user {}",
+            existing.method, existing.username
+        )));
+    }
+
+    // The account doesn't store the sign in methods the authentication service offers.
+    let services =
+        get_cached_services_info(http_client, &account.services.activation_url, services_info)
+            .await
+            .context("get_services_info failed")?;
+
+    let signer = make_signer(&account.user_credentials.private_auth_key)?;
+
+    let username = add_sign_in(
+        http_client,
+        &signer,
+        &services.auth_service,
+        &account.user_credentials.user,
+        &SignInMethod::AdobeId {
+            username: username.to_string(),
+            password: password.to_string(),
+        },
+    )
+    .await
+    .context("add_sign_in failed")?;
+
+    account.user_credentials.username = Some(username);
+    Ok(())
+}
+
+/// Fulfills the ACSM as the account's device, returning the resources it grants.
+///
+/// When the user has activated the maximum number of devices, the activation limit policy of
+/// `params` may activate another device or replace `*account` with a new anonymous account.
+/// The caller should store `*account` again afterwards, even if fulfilling fails.
+pub async fn fulfill_acsm<H: HttpClient>(
+    http_client: &H,
+    acsm: &Acsm,
+    account: &mut AdobeAccount,
+    params: FulfillParams,
+) -> crate::Result<Vec<Resource>> {
+    // The operator would reject an expired ACSM anyway, but only after the auth round-trips.
+    acsm.check_expiration(params.clock.as_ref())?;
+
+    let device = params.device.as_deref();
+    let err = match fulfill_as_account(http_client, acsm, account, device, &params).await {
+        Err(err) if err.is_activation_limit() => err,
+        result => return result,
+    };
+
+    let device = recover_from_activation_limit(http_client, account, &params, err).await?;
+    fulfill_as_account(http_client, acsm, account, device.as_deref(), &params)
+        .await
+        .map_err(activation_limit_error)
+}
+
+async fn fulfill_as_account<H: HttpClient>(
+    http_client: &H,
+    acsm: &Acsm,
+    account: &mut AdobeAccount,
+    device: Option<&str>,
+    params: &FulfillParams,
+) -> crate::Result<Vec<Resource>> {
+    let signer = make_signer(&account.user_credentials.private_auth_key)?;
+    // Fails before authenticating with the operator if the device is unknown.
+    account.device(device)?;
+
+    let result = with_operator_auth(
+        http_client,
+        &signer,
         acsm.operator_url(),
+        account,
+        params,
+        AdeptStep::Fulfill,
+        async |account| {
+            let device = account.device(device)?;
+            fulfill(
+                http_client,
+                &signer,
+                acsm,
+                &account.user_credentials,
+                device,
+                params.license_signature_policy,
+                &params.services_info,
+            )
+            .await
+            .context("fulfill failed")
+        },
     )
     .await?;
 
-    let result = fulfill(
+    Ok(result.resources)
+}
+
+/// Gets a fresh download link for a resource fulfilled earlier, e.g. after the original one
+/// stopped working. Unlike fulfilling the ACSM again, this works after the ACSM has expired.
+///
+/// The resource is downloaded as the device its license is bound to, unless `params` picks
+/// another device. Resources stored before the device was recorded use the first device.
+///
+/// Returns the resource as issued anew by the operator.
+pub async fn download_resource_again<H: HttpClient>(
+    http_client: &H,
+    account: &mut AdobeAccount,
+    resource: &Resource,
+    params: FulfillParams,
+) -> crate::Result<Resource> {
+    let signer = make_signer(&account.user_credentials.private_auth_key)?;
+    let device = params.device.as_deref().or(resource.device.as_deref());
+    account.device(device)?;
+
+    let result = with_operator_auth(
+        http_client,
+        &signer,
+        &resource.operator_url,
+        account,
+        &params,
+        AdeptStep::DownloadAgain,
+        async |account| {
+            download_again(
+                http_client,
+                &signer,
+                resource,
+                &account.user_credentials.user,
+                account.device(device)?.id(),
+                params.license_signature_policy,
+                &params.services_info,
+            )
+            .await
+            .context("download_again failed")
+        },
+    )
+    .await?;
+
+    result
+        .resources
+        .into_iter()
+        .find(|x| x.resource == resource.resource && x.resource_item == resource.resource_item)
+        .with_context(|| {
+            format!(
+                "operator did not return resource {} item {}",
+                resource.resource, resource.resource_item
+            )
+        })
+}
+
+/// Returns a loaned resource before its loan expires.
+///
+/// The loan is returned as the device it was fulfilled as, or as the first device for loans
+/// stored before the device was recorded.
+pub async fn return_loan<H: HttpClient>(
+    http_client: &H,
+    account: &AdobeAccount,
+    loan: &Loan,
+) -> crate::Result<()> {
+    let signer = make_signer(&account.user_credentials.private_auth_key)?;
+
+    inner_return_loan(
         http_client,
         &signer,
-        &acsm,
-        &account.user_credentials,
-        &account.device_info,
-        &account.activated_device,
+        loan,
+        &account.user_credentials.user,
+        account.device(loan.device.as_deref())?.id(),
     )
     .await
-    .context("fulfill failed")?;
+    .context("return_loan failed")?;
 
-    Ok(result.resources)
+    Ok(())
+}
+
+/// Queries the activation service for the details of the account's user.
+///
+/// `services_info` is used to look up the user info URL of accounts that don't store it.
+pub async fn get_user_info<H: HttpClient>(
+    http_client: &H,
+    account: &AdobeAccount,
+    services_info: &ServicesInfoParams,
+) -> crate::Result<UserInfo> {
+    // Accounts created before the user info URL was stored don't have it.
+    let user_info_url = match &account.services.user_info_url {
+        Some(url) => url.clone(),
+        None => {
+            get_cached_services_info(http_client, &account.services.activation_url, services_info)
+                .await
+                .context("get_services_info failed")?
+                .user_info_url
+        }
+    };
+
+    let signer = make_signer(&account.user_credentials.private_auth_key)?;
+
+    fetch_user_info(
+        http_client,
+        &signer,
+        &user_info_url,
+        &account.user_credentials.user,
+    )
+    .await
+    .context("fetch_user_info failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CreateAccountParams, create_adobe_account, deactivate_adobe_account,
+        download_resource_again, get_user_info, link_adobe_id, return_loan,
+    };
+    use crate::{
+        ActivationLimitPolicy, DownloadInfo, Error, FulfillParams, Loan, Resource,
+        ServicesInfoParams, SignInMethod, SignaturePolicy,
+        http_client::testing::MockHttpClient,
+        testing::{
+            ADEPT_URL, AUTH_URL, DEVICE, OPERATOR_URL, USER, activation_token,
+            cached_services_info, respond_operator_auth, respond_sign_in, test_account,
+            test_device,
+        },
+    };
+
+    const SECOND_DEVICE: &str = "urn:uuid:00000000-0000-0000-0000-000000000002";
+
+    #[tokio::test]
+    async fn test_create_adobe_account_activation_limit() {
+        crate::init_test_rand();
+        let http = MockHttpClient::new();
+        let reused = test_device(SECOND_DEVICE, "cmV1c2Vk").device_info;
+
+        // The user signed in to is kept, so it isn't rotated away from.
+        respond_sign_in(&http, USER);
+        http.respond_error(
+            &format!("{ADEPT_URL}/Activate"),
+            "E_ACT_TOO_MANY_ACTIVATIONS",
+        );
+        let err = create_adobe_account(
+            &http,
+            CreateAccountParams {
+                activation_url: ADEPT_URL.to_string(),
+                sign_in_method: SignInMethod::AdobeId {
+                    username: "reader@example.com".to_string(),
+                    password: "secret".to_string(),
+                },
+                services_info: cached_services_info(),
+                activation_limit_policy: ActivationLimitPolicy::RotateAnonymous,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(err.is_activation_limit());
+        assert!(matches!(err.root(), Error::Adept(_)));
+        assert_eq!(
+            http.urls(),
+            [
+                format!("{AUTH_URL}/SignInDirect"),
+                format!("{ADEPT_URL}/Activate")
+            ]
+        );
+
+        respond_sign_in(&http, USER);
+        http.respond_error(
+            &format!("{ADEPT_URL}/Activate"),
+            "E_ACT_TOO_MANY_ACTIVATIONS",
+        );
+        http.respond(
+            &format!("{ADEPT_URL}/Activate"),
+            &activation_token(USER, SECOND_DEVICE, "cmV1c2Vk"),
+        );
+        let account = create_adobe_account(
+            &http,
+            CreateAccountParams {
+                activation_url: ADEPT_URL.to_string(),
+                services_info: cached_services_info(),
+                activation_limit_policy: ActivationLimitPolicy::ReuseDevice(reused),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("create_adobe_account failed");
+        assert_eq!(account.user_credentials.user, USER);
+        assert_eq!(account.devices.len(), 1);
+        assert_eq!(account.device(None).unwrap().id(), SECOND_DEVICE);
+        assert_eq!(
+            account.device(None).unwrap().device_info.fingerprint,
+            "cmV1c2Vk"
+        );
+        assert!(http.requests().pop().unwrap().body.contains("cmV1c2Vk"));
+    }
+
+    #[tokio::test]
+    async fn test_link_adobe_id() {
+        let http = MockHttpClient::new();
+        let mut account = test_account();
+        let services_info = cached_services_info();
+
+        http.respond_error(&format!("{AUTH_URL}/AddSignInDirect"), "E_AUTH_FAILED");
+        let err = link_adobe_id(
+            &http,
+            &mut account,
+            "reader@example.com",
+            "secret",
+            &services_info,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.step(), Some(crate::AdeptStep::AddSignIn));
+        assert!(account.user_credentials.username.is_none());
+
+        http.respond(&format!("{AUTH_URL}/AddSignInDirect"), "<success/>");
+        link_adobe_id(
+            &http,
+            &mut account,
+            "reader@example.com",
+            "secret",
+            &services_info,
+        )
+        .await
+        .expect("link_adobe_id failed");
+
+        let username = account.user_credentials.username.as_ref().unwrap();
+        assert_eq!(username.method, "AdobeID");
+        assert_eq!(username.username, "reader@example.com");
+        // The user and its keys are kept, so earlier licenses stay usable.
+        assert_eq!(account.user_credentials.user, USER);
+
+        let body = http.requests().pop().unwrap().body;
+        assert!(body.contains(r#"method="AdobeID""#));
+        assert!(body.contains(USER));
+        assert!(!body.contains("secret"));
+
+        // The account can't be linked twice.
+        assert!(
+            link_adobe_id(
+                &http,
+                &mut account,
+                "other@example.com",
+                "secret",
+                &services_info
+            )
+            .await
+            .is_err()
+        );
+        assert_eq!(
+            http.urls(),
+            [
+                format!("{AUTH_URL}/AddSignInDirect"),
+                format!("{AUTH_URL}/AddSignInDirect")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deactivate_adobe_account() {
+        let http = MockHttpClient::new();
+        let mut account = test_account();
+        account.devices.push(test_device(
+            "urn:uuid:00000000-0000-0000-0000-000000000002",
+            "c2Vjb25k",
+        ));
+
+        http.respond(&format!("{ADEPT_URL}/Deactivate"), "<success/>");
+        deactivate_adobe_account(&http, &account, None)
+            .await
+            .expect("deactivate_adobe_account failed");
+        let body = http.requests().pop().unwrap().body;
+        assert!(body.contains("urn:uuid:00000000-0000-0000-0000-000000000001"));
+        assert!(body.contains("<adept:signature>"));
+
+        http.respond(&format!("{ADEPT_URL}/Deactivate"), "<success/>");
+        deactivate_adobe_account(
+            &http,
+            &account,
+            Some("urn:uuid:00000000-0000-0000-0000-000000000002"),
+        )
+        .await
+        .expect("deactivate_adobe_account failed");
+        let body = http.requests().pop().unwrap().body;
+        assert!(body.contains("urn:uuid:00000000-0000-0000-0000-000000000002"));
+        assert!(body.contains("c2Vjb25k"));
+        assert!(!body.contains("urn:uuid:00000000-0000-0000-0000-000000000001"));
+
+        // Unknown devices fail before anything is sent.
+        assert!(
+            deactivate_adobe_account(&http, &account, Some("urn:uuid:unknown"))
+                .await
+                .is_err()
+        );
+        assert_eq!(http.urls().len(), 2);
+
+        // The server's refusal is reported as is.
+        http.respond_error(&format!("{ADEPT_URL}/Deactivate"), "E_ACT_NOT_ACTIVATED");
+        let err = deactivate_adobe_account(&http, &account, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.step(), Some(crate::AdeptStep::Deactivate));
+        assert_eq!(
+            err.adept_error().map(|x| &x.code),
+            Some(&crate::AdeptErrorCode::Other(
+                "E_ACT_NOT_ACTIVATED".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_return_loan() {
+        let http = MockHttpClient::new();
+        let account = test_account();
+        let loan = Loan {
+            fulfillment: "urn:uuid:0b3fbd1f-1d4b-4ba5-9f7b-4d2bdfbcc0a5".to_string(),
+            operator_url: OPERATOR_URL.to_string(),
+            device: None,
+        };
+
+        http.respond(
+            &format!("{OPERATOR_URL}/LoanReturn"),
+            r#"<envelope xmlns="http://ns.adobe.com/adept">
+  <loanReturnResult>
+    <notify critical="yes">
+      <notifyURL>https://acs.example.com/fulfillment/Notify</notifyURL>
+      <body><fulfillment>urn:uuid:0b3fbd1f-1d4b-4ba5-9f7b-4d2bdfbcc0a5</fulfillment></body>
+    </notify>
+  </loanReturnResult>
+</envelope>"#,
+        );
+        http.respond(&format!("{OPERATOR_URL}/Notify"), "<success/>");
+        return_loan(&http, &account, &loan)
+            .await
+            .expect("return_loan failed");
+
+        let requests = http.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].body.contains(&loan.fulfillment));
+        assert!(
+            requests[0]
+                .body
+                .contains("urn:uuid:00000000-0000-0000-0000-000000000001")
+        );
+        assert_eq!(requests[1].url, format!("{OPERATOR_URL}/Notify"));
+
+        // The loan can't be returned twice.
+        http.respond_error(
+            &format!("{OPERATOR_URL}/LoanReturn"),
+            "E_LIC_ALREADY_RETURNED",
+        );
+        let err = return_loan(&http, &account, &loan).await.unwrap_err();
+        assert_eq!(err.step(), Some(crate::AdeptStep::ReturnLoan));
+    }
+
+    #[tokio::test]
+    async fn test_return_loan_of_second_device() {
+        let http = MockHttpClient::new();
+        let mut account = test_account();
+        account.devices.push(test_device(SECOND_DEVICE, "c2Vjb25k"));
+        let loan = Loan {
+            fulfillment: "urn:uuid:0b3fbd1f-1d4b-4ba5-9f7b-4d2bdfbcc0a5".to_string(),
+            operator_url: OPERATOR_URL.to_string(),
+            device: Some(SECOND_DEVICE.to_string()),
+        };
+
+        // The loan is returned as the device it was fulfilled as, not the first one.
+        http.respond(
+            &format!("{OPERATOR_URL}/LoanReturn"),
+            r#"<envelope xmlns="http://ns.adobe.com/adept"/>"#,
+        );
+        return_loan(&http, &account, &loan)
+            .await
+            .expect("return_loan failed");
+        let body = http.requests().pop().unwrap().body;
+        assert!(body.contains(&format!("<adept:device>{SECOND_DEVICE}</adept:device>")));
+        assert!(!body.contains(DEVICE));
+
+        // A loan of a device the account no longer has can't be returned.
+        account.devices.pop();
+        assert!(return_loan(&http, &account, &loan).await.is_err());
+        assert_eq!(http.urls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_user_info() {
+        let http = MockHttpClient::new();
+        let account = test_account();
+
+        http.respond(
+            &format!("{ADEPT_URL}/UserInfo"),
+            &format!(
+                r#"<userInfo xmlns="http://ns.adobe.com/adept">
+  <user>{USER}</user>
+  <username method="AdobeID">reader@example.com</username>
+  <activationCount>3</activationCount>
+  <maxActivationCount>6</maxActivationCount>
+</userInfo>"#
+            ),
+        );
+        let user_info = get_user_info(&http, &account, &ServicesInfoParams::default())
+            .await
+            .expect("get_user_info failed");
+        assert_eq!(user_info.user, USER);
+        assert_eq!(user_info.sign_in_method, "AdobeID");
+        assert_eq!(user_info.username.as_deref(), Some("reader@example.com"));
+        assert_eq!(user_info.activation_count, Some(3));
+        assert_eq!(user_info.max_activation_count, Some(6));
+
+        let body = http.requests().pop().unwrap().body;
+        assert!(body.contains(USER));
+        assert!(body.contains("<adept:signature>"));
+
+        // Anonymous users have no username.
+        http.respond(
+            &format!("{ADEPT_URL}/UserInfo"),
+            &format!(
+                r#"<userInfo xmlns="http://ns.adobe.com/adept"><user>{USER}</user></userInfo>"#
+            ),
+        );
+        let user_info = get_user_info(&http, &account, &ServicesInfoParams::default())
+            .await
+            .expect("get_user_info failed");
+        assert_eq!(user_info.sign_in_method, "anonymous");
+        assert!(user_info.username.is_none());
+        assert!(user_info.activation_count.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_user_info_of_legacy_account() {
+        let http = MockHttpClient::new();
+        let mut account = test_account();
+        account.services.user_info_url = None;
+
+        // The user info URL comes from the cached services info, without querying it.
+        http.respond(
+            &format!("{ADEPT_URL}/UserInfo"),
+            &format!(
+                r#"<userInfo xmlns="http://ns.adobe.com/adept"><user>{USER}</user></userInfo>"#
+            ),
+        );
+        let user_info = get_user_info(&http, &account, &cached_services_info())
+            .await
+            .expect("get_user_info failed");
+        assert_eq!(user_info.user, USER);
+        assert_eq!(http.urls(), [format!("{ADEPT_URL}/UserInfo")]);
+    }
+
+    fn license_token(voucher: &str) -> String {
+        format!(
+            r#"<licenseToken xmlns="http://ns.adobe.com/adept"><user>{USER}</user><resource>urn:uuid:00000000-0000-0000-0000-00000000000a</resource><resourceItemType>application/epub+zip</resourceItemType><deviceType>standalone</deviceType><device>urn:uuid:00000000-0000-0000-0000-000000000001</device><voucher>{voucher}</voucher><licenseURL>https://nasigningservice.adobe.com/licensesign</licenseURL><operatorURL>{OPERATOR_URL}</operatorURL><fulfillment>urn:uuid:0b3fbd1f-1d4b-4ba5-9f7b-4d2bdfbcc0a5</fulfillment><distributor>urn:uuid:00000000-0000-0000-0000-00000000000d</distributor><encryptedKey keyInfo="urn:uuid:00000000-0000-0000-0000-00000000000c">a2V5</encryptedKey><model>1</model><signature>c2lnbmF0dXJl</signature></licenseToken>"#
+        )
+    }
+
+    /// Queues the operator's response to downloading the test resource again.
+    fn respond_download_again(http: &MockHttpClient) {
+        http.respond(
+            &format!("{OPERATOR_URL}/DownloadAgain"),
+            &format!(
+                r#"<envelope xmlns="http://ns.adobe.com/adept">
+  <fulfillmentResult>
+    <fulfillment>urn:uuid:0b3fbd1f-1d4b-4ba5-9f7b-4d2bdfbcc0a5</fulfillment>
+    <returnable>false</returnable>
+    <initial>false</initial>
+    <resourceItemInfo>
+      <resource>urn:uuid:00000000-0000-0000-0000-00000000000a</resource>
+      <resourceItem>0</resourceItem>
+      <src>https://acs.example.com/media/fresh.epub</src>
+      <downloadType>simple</downloadType>
+      {}
+    </resourceItemInfo>
+  </fulfillmentResult>
+</envelope>"#,
+                license_token("urn:uuid:00000000-0000-0000-0000-0000000000bb")
+            ),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_resource_again() {
+        let http = MockHttpClient::new();
+        let mut account = test_account();
+
+        // Resource as stored after an earlier fulfillment, long after the ACSM expired.
+        let stored_token = license_token("urn:uuid:00000000-0000-0000-0000-00000000000b");
+        let resource: Resource = serde_json::from_value(serde_json::json!({
+            "resource": "urn:uuid:00000000-0000-0000-0000-00000000000a",
+            "resource_item": 0,
+            "item_type": "application/epub+zip",
+            "encrypted_key": { "encrypted_key": "a2V5" },
+            "download": { "simple": "https://acs.example.com/media/expired.epub" },
+            "loan": null,
+            "permissions": {},
+            "metadata": { "title": "Example Book" },
+            "fulfillment": "urn:uuid:0b3fbd1f-1d4b-4ba5-9f7b-4d2bdfbcc0a5",
+            "voucher": "urn:uuid:00000000-0000-0000-0000-00000000000b",
+            "distributor": "urn:uuid:00000000-0000-0000-0000-00000000000d",
+            "license_url": "https://nasigningservice.adobe.com/licensesign",
+            "operator_url": OPERATOR_URL,
+            "license_token": stored_token,
+        }))
+        .expect("could not load stored resource");
+
+        respond_operator_auth(&http);
+        respond_download_again(&http);
+
+        let downloaded = download_resource_again(
+            &http,
+            &mut account,
+            &resource,
+            FulfillParams {
+                license_signature_policy: SignaturePolicy::Skip,
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("download_resource_again failed");
+
+        // The stored license token is sent back as it was issued.
+        let request = http.requests().pop().unwrap();
+        assert_eq!(request.url, format!("{OPERATOR_URL}/DownloadAgain"));
+        assert!(request.body.contains(&stored_token));
+        assert!(request.body.contains("<adept:signature>"));
+
+        let DownloadInfo::Simple(src) = &downloaded.download;
+        assert_eq!(src, "https://acs.example.com/media/fresh.epub");
+        assert_eq!(
+            downloaded.voucher,
+            "urn:uuid:00000000-0000-0000-0000-0000000000bb"
+        );
+        assert_eq!(downloaded.encrypted_key.encrypted_key, b"key");
+        assert!(downloaded.license_token.contains("0000000000bb"));
+        // Resources stored before the device was recorded use the first device.
+        assert!(
+            request
+                .body
+                .contains(&format!("<adept:device>{DEVICE}</adept:device>"))
+        );
+        assert_eq!(downloaded.device.as_deref(), Some(DEVICE));
+    }
+
+    #[tokio::test]
+    async fn test_download_resource_again_as_its_device() {
+        let http = MockHttpClient::new();
+        let mut account = test_account();
+        account.devices.push(test_device(SECOND_DEVICE, "c2Vjb25k"));
+        let params = || FulfillParams {
+            license_signature_policy: SignaturePolicy::Skip,
+            ..Default::default()
+        };
+
+        let resource: Resource = serde_json::from_value(serde_json::json!({
+            "resource": "urn:uuid:00000000-0000-0000-0000-00000000000a",
+            "resource_item": 0,
+            "item_type": "application/epub+zip",
+            "encrypted_key": { "encrypted_key": "a2V5" },
+            "download": { "simple": "https://acs.example.com/media/expired.epub" },
+            "loan": null,
+            "permissions": {},
+            "metadata": {},
+            "fulfillment": "urn:uuid:0b3fbd1f-1d4b-4ba5-9f7b-4d2bdfbcc0a5",
+            "voucher": "urn:uuid:00000000-0000-0000-0000-00000000000b",
+            "distributor": "urn:uuid:00000000-0000-0000-0000-00000000000d",
+            "license_url": "https://nasigningservice.adobe.com/licensesign",
+            "operator_url": OPERATOR_URL,
+            "license_token": license_token("urn:uuid:00000000-0000-0000-0000-00000000000b"),
+            "device": SECOND_DEVICE,
+        }))
+        .expect("could not load stored resource");
+
+        // The resource is downloaded as the device its license is bound to.
+        respond_operator_auth(&http);
+        respond_download_again(&http);
+        let downloaded = download_resource_again(&http, &mut account, &resource, params())
+            .await
+            .expect("download_resource_again failed");
+        let body = http.requests().pop().unwrap().body;
+        assert!(body.contains(&format!("<adept:device>{SECOND_DEVICE}</adept:device>")));
+        assert_eq!(downloaded.device.as_deref(), Some(SECOND_DEVICE));
+
+        // Unless another device is picked explicitly.
+        respond_download_again(&http);
+        let downloaded = download_resource_again(
+            &http,
+            &mut account,
+            &downloaded,
+            FulfillParams {
+                device: Some(DEVICE.to_string()),
+                ..params()
+            },
+        )
+        .await
+        .expect("download_resource_again failed");
+        let body = http.requests().pop().unwrap().body;
+        assert!(body.contains(&format!("<adept:device>{DEVICE}</adept:device>")));
+        assert_eq!(downloaded.device.as_deref(), Some(DEVICE));
+    }
 }
@@ -1,19 +1,30 @@
-use crate::adept::ResourceItemInfo;
+use std::collections::HashMap;
 
-use super::{DeviceInfo, HttpClient, UserCredentials, adept, make_expiration, random_nonce};
+use crate::adept::{Permissions, ResourceItemInfo, ResourceMetadata};
+use crate::serializarion::serde_base64;
+use crate::services::{ServicesInfoParams, get_cached_license_certificate};
+
+use super::{AccountDevice, HttpClient, UserCredentials, adept, make_expiration, random_nonce};
 
 use adobededrmtools_crypto::{Signer, b64, unb64};
-use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use xmltree::Element;
+
+use crate::{
+    AdeptStep, Error,
+    error::{Context, StepContext},
+};
 
 pub async fn fulfillment_auth<H: HttpClient>(
     http_client: &H,
-    acsm: &adept::Acsm,
+    operator_url: &str,
     credentials: &UserCredentials,
     auth_certificate: &[u8],
-) -> anyhow::Result<()> {
+) -> crate::Result<()> {
     adept::fulfillment_auth(
         http_client,
-        acsm.operator_url(),
+        operator_url,
         adept::FulfillmentAuthData {
             user: credentials.user.clone(),
             certificate: b64(&credentials.user_certificate),
@@ -22,7 +33,7 @@ pub async fn fulfillment_auth<H: HttpClient>(
         },
     )
     .await
-    .context("fulfillment auth failed")?;
+    .step(AdeptStep::FulfillmentAuth)?;
 
     Ok(())
 }
@@ -33,7 +44,7 @@ pub async fn init_license_service<H: HttpClient>(
     activation_url: &str,
     user: &str,
     operator_url: &str,
-) -> anyhow::Result<()> {
+) -> crate::Result<()> {
     adept::init_license_service(
         http_client,
         signer,
@@ -46,25 +57,72 @@ pub async fn init_license_service<H: HttpClient>(
         },
     )
     .await
-    .context("init_license_service failed")?;
+    .step(AdeptStep::InitLicenseService)?;
 
     Ok(())
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Resource {
     pub resource: String,
+    /// Index of the item within the resource.
+    pub resource_item: u32,
     pub item_type: String,
     pub encrypted_key: ResourceEncryptedKey,
     pub download: DownloadInfo,
+    /// Present if the resource is a returnable loan.
+    pub loan: Option<Loan>,
+    pub permissions: Permissions,
+    pub metadata: ResourceMetadata,
+    /// Fulfillment id assigned by the operator.
+    pub fulfillment: String,
+    pub voucher: String,
+    pub distributor: String,
+    pub license_url: String,
+    /// Operator the resource was fulfilled by, which can download it again.
+    pub operator_url: String,
+    /// Raw `licenseToken` element, as issued by the operator.
+    pub license_token: String,
+    /// Id of the account's device the resource was fulfilled as, which the license is bound to.
+    /// Unknown for resources stored before it was recorded.
+    #[serde(default)]
+    pub device: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+impl Resource {
+    /// Time the license stops allowing to display the resource, e.g. the end of a loan.
+    ///
+    /// That's the latest limit of the display permissions, or `None` if any of them is unlimited.
+    pub fn expiration(&self) -> Option<DateTime<Utc>> {
+        self.permissions
+            .display
+            .iter()
+            .map(|x| x.until)
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .max()
+    }
+}
+
+/// Loan that can be returned with [`crate::return_loan`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Loan {
+    pub fulfillment: String,
+    pub operator_url: String,
+    /// Id of the account's device the loan was fulfilled as, which has to return it.
+    /// Unknown for loans stored before it was recorded.
+    #[serde(default)]
+    pub device: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResourceEncryptedKey {
+    #[serde(with = "serde_base64")]
     pub encrypted_key: Vec<u8>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum DownloadInfo {
     Simple(String),
 }
@@ -74,15 +132,124 @@ pub struct FulfillmentResult {
     pub resources: Vec<Resource>,
 }
 
+/// What to do when a license token doesn't match the signature of the license service.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SignaturePolicy {
+    /// Fail the fulfillment.
+    Enforce,
+    /// Log a warning and keep the resources.
+    #[default]
+    Warn,
+    /// Don't check the signatures.
+    Skip,
+}
+
+async fn verify_license_tokens<H: HttpClient>(
+    http_client: &H,
+    resources: &[ResourceItemInfo],
+    resource_items: &[Element],
+    policy: SignaturePolicy,
+    services_info: &ServicesInfoParams,
+) -> crate::Result<()> {
+    if policy == SignaturePolicy::Skip {
+        return Ok(());
+    }
+
+    // Certificates by license URL. The resources of one fulfillment usually share it, so it's
+    // only looked up once even without a cache.
+    let mut certificates = HashMap::new();
+
+    for resource in resources {
+        let item = find_resource_item(resource_items, resource)?;
+        let result = verify_license_token(
+            http_client,
+            &mut certificates,
+            &resource.license_token,
+            item.get_child("licenseToken"),
+            services_info,
+        )
+        .await
+        .with_context(|| format!("license token of {}", resource.resource));
+        check_signature_result(result, policy)?;
+    }
+
+    Ok(())
+}
+
+/// Finds the raw `resourceItemInfo` element of a resource by its `resource` and `resourceItem`.
+fn find_resource_item<'a>(
+    resource_items: &'a [Element],
+    resource: &ResourceItemInfo,
+) -> crate::Result<&'a Element> {
+    let text = |item: &Element, name: &str| {
+        item.get_child(name)
+            .and_then(|x| x.get_text())
+            .map(|x| x.trim().to_string())
+    };
+
+    resource_items
+        .iter()
+        .find(|item| {
+            text(item, "resource").as_deref() == Some(resource.resource.as_str())
+                && text(item, "resourceItem") == Some(resource.resource_item.to_string())
+        })
+        .ok_or_else(|| {
+            Error::Protocol(format!(
+                "no resource item element for resource {} item {}",
+                resource.resource, resource.resource_item
+            ))
+        })
+}
+
+fn check_signature_result(result: crate::Result<()>, policy: SignaturePolicy) -> crate::Result<()> {
+    match result {
+        Err(err) if policy == SignaturePolicy::Enforce => Err(err),
+        Err(err) => {
+            log::warn!("Could not verify license token signature: {}", err);
+            Ok(())
+        }
+        Ok(()) => Ok(()),
+    }
+}
+
+async fn verify_license_token<H: HttpClient>(
+    http_client: &H,
+    certificates: &mut HashMap<String, Vec<u8>>,
+    token: &adept::LicenseToken,
+    element: Option<&Element>,
+    services_info: &ServicesInfoParams,
+) -> crate::Result<()> {
+    let element = element.context("no license token element")?;
+
+    if !certificates.contains_key(&token.license_url) {
+        let certificate =
+            get_cached_license_certificate(http_client, &token.license_url, services_info).await?;
+        certificates.insert(token.license_url.clone(), certificate);
+    }
+    let certificate = &certificates[&token.license_url];
+
+    if !adept::verify_element_signature(certificate, element, &token.signature)? {
+        return Err(Error::SignatureMismatch(format!(
+            "license token of {}",
+            token.resource
+        )));
+    }
+
+    Ok(())
+}
+
 pub async fn fulfill<H: HttpClient>(
     http_client: &H,
     signer: &Signer,
     acsm: &adept::Acsm,
     credentials: &UserCredentials,
-    device_info: &DeviceInfo,
-    activated_device: &str,
-) -> anyhow::Result<FulfillmentResult> {
-    let envelope = adept::fulfill(
+    device: &AccountDevice,
+    signature_policy: SignaturePolicy,
+    services_info: &ServicesInfoParams,
+) -> crate::Result<FulfillmentResult> {
+    let device_info = &device.device_info;
+    let activated_device = device.id();
+    let response = adept::fulfill(
         http_client,
         signer,
         acsm.operator_url(),
@@ -99,28 +266,127 @@ pub async fn fulfill<H: HttpClient>(
         },
     )
     .await
-    .context("fulfill request failed")?;
+    .step(AdeptStep::Fulfill)?;
 
-    log::debug!("envelope: {:?}", envelope);
+    process_fulfill_response(
+        http_client,
+        signer,
+        response,
+        &credentials.user,
+        activated_device,
+        signature_policy,
+        services_info,
+    )
+    .await
+}
 
-    Ok(FulfillmentResult {
-        resources: envelope
-            .fulfillmen_result
-            .resources
-            .into_iter()
-            .map(|resource| convert_resource(resource))
-            .collect::<anyhow::Result<Vec<_>>>()?,
-    })
+/// Gets a fresh copy of a resource fulfilled earlier, without the ACSM it was fulfilled with.
+pub async fn download_again<H: HttpClient>(
+    http_client: &H,
+    signer: &Signer,
+    resource: &Resource,
+    user: &str,
+    activated_device: &str,
+    signature_policy: SignaturePolicy,
+    services_info: &ServicesInfoParams,
+) -> crate::Result<FulfillmentResult> {
+    let response = adept::download_again(
+        http_client,
+        signer,
+        &resource.operator_url,
+        adept::DownloadAgainData {
+            user: user.to_string(),
+            device: activated_device.to_string(),
+            fulfillment: resource.fulfillment.clone(),
+            resource: resource.resource.clone(),
+            license_token: resource.license_token.clone(),
+            nonce: random_nonce(),
+            expiration: make_expiration(),
+        },
+    )
+    .await
+    .step(AdeptStep::DownloadAgain)?;
+
+    process_fulfill_response(
+        http_client,
+        signer,
+        response,
+        user,
+        activated_device,
+        signature_policy,
+        services_info,
+    )
+    .await
+}
+
+async fn process_fulfill_response<H: HttpClient>(
+    http_client: &H,
+    signer: &Signer,
+    response: adept::FulfillResponse,
+    user: &str,
+    activated_device: &str,
+    signature_policy: SignaturePolicy,
+    services_info: &ServicesInfoParams,
+) -> crate::Result<FulfillmentResult> {
+    log::debug!("envelope: {:?}", response.envelope);
+
+    verify_license_tokens(
+        http_client,
+        &response.envelope.fulfillmen_result.resources,
+        &response.resource_items,
+        signature_policy,
+        services_info,
+    )
+    .await
+    .context("license token verification failed")?;
+
+    let result = response.envelope.fulfillmen_result;
+    let fulfillment = result.returnable.then_some(result.fulfillment.as_str());
+
+    // The notifications tell the distributor the resources were delivered, so they are only
+    // sent once the resources are known to be usable.
+    let resources = result
+        .resources
+        .into_iter()
+        .map(|resource| {
+            let element = find_resource_item(&response.resource_items, &resource)?;
+            convert_resource(
+                resource,
+                element,
+                &result.fulfillment,
+                fulfillment,
+                activated_device,
+            )
+        })
+        .collect::<crate::Result<Vec<_>>>()?;
+
+    send_notifications(
+        http_client,
+        signer,
+        &response.notifications,
+        user,
+        activated_device,
+    )
+    .await
+    .context("fulfillment notification failed")?;
+
+    Ok(FulfillmentResult { resources })
 }
 
-fn convert_resource(item: ResourceItemInfo) -> anyhow::Result<Resource> {
+fn convert_resource(
+    item: ResourceItemInfo,
+    element: &Element,
+    fulfillment: &str,
+    loan: Option<&str>,
+    device: &str,
+) -> crate::Result<Resource> {
     let download = match item.download_type.as_str() {
         "simple" => DownloadInfo::Simple(item.src),
         _ => {
-            return Err(anyhow::anyhow!(
-                "unsupported download type: {}",
+            return Err(Error::Unsupported(format!(
+                "download type: {}",
                 item.download_type
-            ));
+            )));
         }
     };
 
@@ -128,10 +394,403 @@ fn convert_resource(item: ResourceItemInfo) -> anyhow::Result<Resource> {
         encrypted_key: unb64(&item.license_token.encrypted_key.key)?,
     };
 
+    let loan = loan.map(|fulfillment| Loan {
+        fulfillment: fulfillment.to_string(),
+        operator_url: item.license_token.operator_url.clone(),
+        device: Some(device.to_string()),
+    });
+
+    let license_token_element = element
+        .get_child("licenseToken")
+        .context("resource item has no license token")?;
+    let license_token =
+        adept::write_element(license_token_element).context("could not write license token")?;
+
+    let permissions = license_token_element
+        .get_child("permissions")
+        .map(adept::parse_permissions)
+        .unwrap_or_default();
+
+    let metadata = element
+        .get_child("metadata")
+        .map(adept::parse_metadata)
+        .unwrap_or_default();
+
+    let token = item.license_token;
     Ok(Resource {
         resource: item.resource,
-        item_type: item.license_token.resource_item_type,
+        resource_item: item.resource_item,
+        item_type: token.resource_item_type,
         encrypted_key,
         download,
+        loan,
+        permissions,
+        metadata,
+        fulfillment: fulfillment.to_string(),
+        voucher: token.voucher,
+        distributor: token.distributor,
+        license_url: token.license_url,
+        operator_url: token.operator_url,
+        license_token,
+        device: Some(device.to_string()),
     })
 }
+
+pub async fn return_loan<H: HttpClient>(
+    http_client: &H,
+    signer: &Signer,
+    loan: &Loan,
+    user: &str,
+    activated_device: &str,
+) -> crate::Result<()> {
+    let notifications = adept::return_loan(
+        http_client,
+        signer,
+        &loan.operator_url,
+        adept::LoanReturnData {
+            user: user.to_string(),
+            device: activated_device.to_string(),
+            loan: loan.fulfillment.clone(),
+            nonce: random_nonce(),
+            expiration: make_expiration(),
+        },
+    )
+    .await
+    .step(AdeptStep::ReturnLoan)?;
+
+    send_notifications(http_client, signer, &notifications, user, activated_device)
+        .await
+        .context("loan return notification failed")?;
+
+    Ok(())
+}
+
+/// Sends the notifications the operator asked for.
+/// Failure of a critical notification fails the whole operation.
+async fn send_notifications<H: HttpClient>(
+    http_client: &H,
+    signer: &Signer,
+    notifications: &[adept::Notification],
+    user: &str,
+    activated_device: &str,
+) -> crate::Result<()> {
+    for notification in notifications {
+        let Some(body) = &notification.body else {
+            // There is nothing to send, but the operator expects the notification.
+            if notification.critical {
+                return Err(Error::Protocol(format!(
+                    "critical notification has no body: {}",
+                    notification.notify_url
+                )));
+            }
+            log::debug!(
+                "skipping notification without body: {}",
+                notification.notify_url
+            );
+            continue;
+        };
+
+        let result = adept::notify(
+            http_client,
+            signer,
+            &notification.notify_url,
+            adept::NotifyData {
+                user: user.to_string(),
+                device: activated_device.to_string(),
+                body: body.clone(),
+                nonce: random_nonce(),
+                expiration: make_expiration(),
+            },
+        )
+        .await
+        .step(AdeptStep::Notify);
+
+        match result {
+            Ok(()) => log::debug!("notified {}", notification.notify_url),
+            Err(err) if notification.critical => {
+                return Err(Error::Context {
+                    context: format!("critical notification failed: {}", notification.notify_url),
+                    source: Box::new(err),
+                });
+            }
+            Err(err) => log::warn!(
+                "Non-critical notification to {} failed: {:?}",
+                notification.notify_url,
+                err
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use xmltree::Element;
+
+    use super::{SignaturePolicy, convert_resource, process_fulfill_response, send_notifications};
+    use crate::{
+        adept::{Envelope, FulfillResponse, Notification, ResourceItemInfo},
+        http_client::testing::MockHttpClient,
+        make_signer,
+        services::ServicesInfoParams,
+        testing::{DEVICE, USER, USER_KEY},
+    };
+
+    const NOTIFY_URL: &str = "https://acs.example.com/fulfillment/Notify";
+    const BOOK: &str = "urn:uuid:00000000-0000-0000-0000-00000000000a";
+    const DISTRIBUTOR_NOTIFY_URL: &str = "https://distributor.example.com/Notify";
+
+    fn notification(notify_url: &str, critical: bool, body: Option<&str>) -> Notification {
+        Notification {
+            notify_url: notify_url.to_string(),
+            critical,
+            body: body.map(|x| x.to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_notifications() {
+        crate::init_test_rand();
+        let signer = make_signer(USER_KEY).unwrap();
+        let http = MockHttpClient::new();
+        let body = "<body><transaction>61777-38641</transaction></body>";
+
+        // Failed non-critical notifications are only logged.
+        http.respond(NOTIFY_URL, "<success/>");
+        http.respond_error(DISTRIBUTOR_NOTIFY_URL, "E_DISTRIBUTOR_UNAVAILABLE");
+        let notifications = [
+            notification(NOTIFY_URL, true, Some(body)),
+            notification(DISTRIBUTOR_NOTIFY_URL, false, Some(body)),
+            notification(DISTRIBUTOR_NOTIFY_URL, false, None),
+        ];
+        send_notifications(&http, &signer, &notifications, USER, DEVICE)
+            .await
+            .expect("send_notifications failed");
+
+        let requests = http.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].url, NOTIFY_URL);
+        assert!(requests[0].body.contains(body));
+        assert!(requests[0].body.contains(USER));
+        assert!(requests[0].body.contains(DEVICE));
+        assert!(requests[0].body.contains("<adept:signature>"));
+
+        // Failed critical notifications fail the operation.
+        http.respond_error(NOTIFY_URL, "E_STREAM_NOT_OPEN");
+        let notifications = [notification(NOTIFY_URL, true, Some(body))];
+        let err = send_notifications(&http, &signer, &notifications, USER, DEVICE)
+            .await
+            .unwrap_err();
+        assert_eq!(err.step(), Some(crate::AdeptStep::Notify));
+
+        // So do critical notifications that can't be sent.
+        let notifications = [notification(NOTIFY_URL, true, None)];
+        assert!(
+            send_notifications(&http, &signer, &notifications, USER, DEVICE)
+                .await
+                .is_err()
+        );
+        assert_eq!(http.requests().len(), 3);
+    }
+
+    fn resource_item_info(resource: &str, download_type: &str) -> String {
+        format!(
+            r#"<resourceItemInfo xmlns="http://ns.adobe.com/adept">
+  <resource>{resource}</resource>
+  <resourceItem>0</resourceItem>
+  <src>https://acs.example.com/media/book.epub</src>
+  <downloadType>{download_type}</downloadType>
+  <licenseToken>
+    <user>urn:uuid:4b4e7bd5-4b2b-4bb5-a2a4-3a0e5f0cbd2b</user>
+    <resource>{resource}</resource>
+    <resourceItemType>application/epub+zip</resourceItemType>
+    <deviceType>standalone</deviceType>
+    <device>urn:uuid:00000000-0000-0000-0000-000000000001</device>
+    <voucher>urn:uuid:00000000-0000-0000-0000-00000000000b</voucher>
+    <licenseURL>https://nasigningservice.adobe.com/licensesign</licenseURL>
+    <operatorURL>https://acs.example.com/fulfillment</operatorURL>
+    <fulfillment>urn:uuid:0b3fbd1f-1d4b-4ba5-9f7b-4d2bdfbcc0a5</fulfillment>
+    <distributor>urn:uuid:00000000-0000-0000-0000-00000000000d</distributor>
+    <encryptedKey keyInfo="urn:uuid:00000000-0000-0000-0000-00000000000c">a2V5</encryptedKey>
+    <model>1</model>
+    <signature>c2lnbmF0dXJl</signature>
+  </licenseToken>
+</resourceItemInfo>"#
+        )
+    }
+
+    /// Fulfillment of `items`, with the raw elements parsed from `elements` and a critical
+    /// notification.
+    fn fulfill_response(items: &[String], elements: &[String]) -> FulfillResponse {
+        let envelope = format!(
+            r#"<envelope xmlns="http://ns.adobe.com/adept">
+  <fulfillmentResult>
+    <fulfillment>urn:uuid:0b3fbd1f-1d4b-4ba5-9f7b-4d2bdfbcc0a5</fulfillment>
+    <returnable>false</returnable>
+    <initial>true</initial>
+    {}
+  </fulfillmentResult>
+</envelope>"#,
+            items.join("\n")
+        );
+        FulfillResponse {
+            envelope: quick_xml::de::from_str::<Envelope>(&envelope).unwrap(),
+            notifications: vec![notification(NOTIFY_URL, true, Some("<body/>"))],
+            resource_items: elements
+                .iter()
+                .map(|x| Element::parse(x.as_bytes()).unwrap())
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_notify_after_converting_resources() {
+        crate::init_test_rand();
+        let signer = make_signer(USER_KEY).unwrap();
+        let http = MockHttpClient::new();
+        let response = |download_type: &str| {
+            let item = [resource_item_info(BOOK, download_type)];
+            fulfill_response(&item, &item)
+        };
+
+        // The distributor isn't told about resources that can't be used.
+        let result = process_fulfill_response(
+            &http,
+            &signer,
+            response("streaming"),
+            USER,
+            DEVICE,
+            SignaturePolicy::Skip,
+            &ServicesInfoParams::default(),
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(http.urls().is_empty());
+
+        http.respond(NOTIFY_URL, "<success/>");
+        let result = process_fulfill_response(
+            &http,
+            &signer,
+            response("simple"),
+            USER,
+            DEVICE,
+            SignaturePolicy::Skip,
+            &ServicesInfoParams::default(),
+        )
+        .await
+        .expect("process_fulfill_response failed");
+        assert_eq!(result.resources.len(), 1);
+        assert_eq!(http.urls(), [NOTIFY_URL]);
+    }
+
+    #[tokio::test]
+    async fn test_match_resource_items() {
+        const OTHER_BOOK: &str = "urn:uuid:00000000-0000-0000-0000-0000000000aa";
+
+        crate::init_test_rand();
+        let signer = make_signer(USER_KEY).unwrap();
+        let http = MockHttpClient::new();
+        let (book, other_book) = (
+            resource_item_info(BOOK, "simple"),
+            resource_item_info(OTHER_BOOK, "simple"),
+        );
+
+        // Each resource gets the license token of its own element, whatever their order.
+        http.respond(NOTIFY_URL, "<success/>");
+        let result = process_fulfill_response(
+            &http,
+            &signer,
+            fulfill_response(
+                &[book.clone(), other_book.clone()],
+                &[other_book.clone(), book.clone()],
+            ),
+            USER,
+            DEVICE,
+            SignaturePolicy::Skip,
+            &ServicesInfoParams::default(),
+        )
+        .await
+        .expect("process_fulfill_response failed");
+        assert_eq!(result.resources.len(), 2);
+        for resource in &result.resources {
+            assert!(resource.license_token.contains(&resource.resource));
+        }
+        assert_eq!(result.resources[1].resource, OTHER_BOOK);
+
+        // A resource without an element of its own fails, before anyone is notified.
+        let err = process_fulfill_response(
+            &http,
+            &signer,
+            fulfill_response(&[book.clone(), other_book], &[book.clone(), book]),
+            USER,
+            DEVICE,
+            SignaturePolicy::Skip,
+            &ServicesInfoParams::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, crate::Error::Protocol(_)));
+        assert_eq!(http.urls(), [NOTIFY_URL]);
+    }
+
+    #[test]
+    fn test_convert_resource_permissions() {
+        const RESOURCE_ITEM: &str = r#"<resourceItemInfo xmlns="http://ns.adobe.com/adept">
+  <resource>urn:uuid:00000000-0000-0000-0000-00000000000a</resource>
+  <resourceItem>0</resourceItem>
+  <metadata>
+    <dc:title xmlns:dc="http://purl.org/dc/elements/1.1/">Example Book</dc:title>
+  </metadata>
+  <src>https://acs.example.com/media/book.epub</src>
+  <downloadType>simple</downloadType>
+  <licenseToken>
+    <user>urn:uuid:4b4e7bd5-4b2b-4bb5-a2a4-3a0e5f0cbd2b</user>
+    <resource>urn:uuid:00000000-0000-0000-0000-00000000000a</resource>
+    <resourceItemType>application/epub+zip</resourceItemType>
+    <deviceType>standalone</deviceType>
+    <device>urn:uuid:00000000-0000-0000-0000-000000000001</device>
+    <voucher>urn:uuid:00000000-0000-0000-0000-00000000000b</voucher>
+    <licenseURL>https://nasigningservice.adobe.com/licensesign</licenseURL>
+    <operatorURL>https://acs.example.com/fulfillment</operatorURL>
+    <fulfillment>urn:uuid:0b3fbd1f-1d4b-4ba5-9f7b-4d2bdfbcc0a5</fulfillment>
+    <distributor>urn:uuid:00000000-0000-0000-0000-00000000000d</distributor>
+    <encryptedKey keyInfo="urn:uuid:00000000-0000-0000-0000-00000000000c">a2V5</encryptedKey>
+    <permissions>
+      <display><until>2025-03-15T18:00:00Z</until></display>
+      <display><until>2025-03-22T18:00:00Z</until></display>
+      <display><until>soon</until></display>
+      <transfer/>
+      <print><count initial="many"/></print>
+    </permissions>
+    <model>1</model>
+    <signature>c2lnbmF0dXJl</signature>
+  </licenseToken>
+</resourceItemInfo>"#;
+
+        let item: ResourceItemInfo = quick_xml::de::from_str(RESOURCE_ITEM).unwrap();
+        let element = Element::parse(RESOURCE_ITEM.as_bytes()).unwrap();
+        let resource = convert_resource(
+            item,
+            &element,
+            "urn:uuid:0b3fbd1f-1d4b-4ba5-9f7b-4d2bdfbcc0a5",
+            None,
+            DEVICE,
+        )
+        .expect("convert_resource failed");
+
+        assert_eq!(resource.metadata.title.as_deref(), Some("Example Book"));
+        assert_eq!(resource.permissions.display.len(), 3);
+        assert_eq!(resource.permissions.print.len(), 1);
+        assert_eq!(resource.permissions.print[0].count, None);
+        // The display permission with the invalid limit counts as unlimited.
+        assert_eq!(resource.expiration(), None);
+
+        let mut resource = resource;
+        resource.permissions.display.pop();
+        assert_eq!(
+            resource.expiration().map(|x| x.to_rfc3339()),
+            Some("2025-03-22T18:00:00+00:00".to_string())
+        );
+    }
+}
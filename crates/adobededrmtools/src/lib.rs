@@ -1,21 +1,51 @@
 mod adept;
 
+mod account;
 mod activation;
+mod activation_limit;
+mod ade;
 mod auth;
+mod clock;
 pub mod dedrm;
+mod error;
 mod facade;
 mod fulfillment;
+mod operator_auth;
 mod serializarion;
 mod services;
+#[cfg(test)]
+mod testing;
+mod user_info;
 
-pub use activation::DeviceInfo;
-pub use adept::{Acsm, DEFAULT_ACTIVATION_URL, HttpClient, http_client};
+pub use account::{AccountDevice, AdobeAccount, AdobeMinServicesInfo, AuthorizedOperator};
+pub use activation::{ActivatedDevice, DeviceInfo};
+pub use activation_limit::{AccountArchive, ActivationLimitPolicy, DirectoryAccountArchive};
+pub use ade::{
+    DeviceIdentity, MacActivation, RegistryActivation, RegistryActivationToken,
+    RegistryCredentials, SecondaryDevice, SecondaryDeviceActivation, import_activation,
+    import_activation_records, parse_mac_activation, parse_registry_activation,
+    write_device_activation,
+};
+pub use adept::{
+    Acsm, AdeptError, AdeptErrorCode, DEFAULT_ACTIVATION_URL, FulfillmentType, HttpClient,
+    Permission, PermissionCount, Permissions, ResourceMetadata, http_client,
+};
 pub use adobededrmtools_crypto::make_signer;
-pub use auth::UserCredentials;
+pub use auth::{AccountUsername, SignInMethod, UserCredentials};
+pub use clock::{Clock, SystemClock};
+pub use error::{AdeptStep, Error, Result};
 pub use facade::{
-    AdobeAccount, AdobeMinServicesInfo, CreateAccountParams, create_adobe_account, fulfill_acsm,
+    CreateAccountParams, FulfillParams, activate_secondary_device, add_account_device,
+    create_adobe_account, deactivate_adobe_account, download_resource_again, export_adobe_account,
+    fulfill_acsm, get_sign_in_methods, get_user_info, link_adobe_id, return_loan,
+};
+pub use fulfillment::{DownloadInfo, Loan, Resource, ResourceEncryptedKey, SignaturePolicy};
+pub use services::{
+    AdobeAuthServiceInfo, AdobeServicesInfo, CachedLicenseCertificate, CachedServicesInfo,
+    FileServicesInfoCache, MemoryServicesInfoCache, ServicesInfoCache, ServicesInfoParams,
+    SignInMethodInfo,
 };
-pub use fulfillment::{DownloadInfo, Resource, ResourceEncryptedKey};
+pub use user_info::UserInfo;
 
 fn random_nonce() -> String {
     use adobededrmtools_crypto::{b64, rand_bytes};
@@ -31,3 +61,10 @@ fn make_expiration() -> String {
     let dt: chrono::DateTime<chrono::Utc> = expiration_instant.into();
     dt.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
 }
+
+#[cfg(test)]
+fn init_test_rand() {
+    // The seed can only be set once per process, and tests share it.
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| adobededrmtools_crypto::init_rand([0; 32]));
+}
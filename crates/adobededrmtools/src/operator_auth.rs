@@ -0,0 +1,231 @@
+use adobededrmtools_crypto::Signer;
+
+use crate::{
+    AdeptErrorCode, AdeptStep, AdobeAccount, Error, FulfillParams, HttpClient,
+    fulfillment::{fulfillment_auth, init_license_service},
+};
+
+/// Makes the operator request, authenticating with the operator first unless the account
+/// has done that recently.
+pub(crate) async fn with_operator_auth<H: HttpClient, T>(
+    http_client: &H,
+    signer: &Signer,
+    operator_url: &str,
+    account: &mut AdobeAccount,
+    params: &FulfillParams,
+    step: AdeptStep,
+    request: impl AsyncFn(&AdobeAccount) -> crate::Result<T>,
+) -> crate::Result<T> {
+    let cached =
+        account.is_operator_authorized(operator_url, params.clock.now(), params.operator_auth_ttl);
+    if cached {
+        log::debug!("Reusing the authentication for operator {}", operator_url);
+    } else {
+        authorize_operator(http_client, signer, operator_url, account).await?;
+        account.set_operator_authorized(operator_url, Some(params.clock.now()));
+    }
+
+    let mut result = request(account).await;
+
+    // The operator may have dropped the authentication before the account's record expired.
+    if cached
+        && let Err(err) = &result
+        && err.step() == Some(step)
+        && is_operator_auth_error(err)
+    {
+        log::info!(
+            "Operator {} rejected the request, authenticating again: {}",
+            operator_url,
+            err
+        );
+        account.set_operator_authorized(operator_url, None);
+        authorize_operator(http_client, signer, operator_url, account).await?;
+        account.set_operator_authorized(operator_url, Some(params.clock.now()));
+
+        result = request(account).await;
+    }
+
+    result
+}
+
+/// Whether the operator rejected the request because it doesn't know the user's authentication.
+fn is_operator_auth_error(err: &Error) -> bool {
+    err.adept_error()
+        .is_some_and(|x| x.code == AdeptErrorCode::AuthFailed)
+}
+
+async fn authorize_operator<H: HttpClient>(
+    http_client: &H,
+    signer: &Signer,
+    operator_url: &str,
+    account: &AdobeAccount,
+) -> crate::Result<()> {
+    fulfillment_auth(
+        http_client,
+        operator_url,
+        &account.user_credentials,
+        &account.services.auth_certificate,
+    )
+    .await?;
+
+    init_license_service(
+        http_client,
+        signer,
+        &account.services.activation_url,
+        &account.user_credentials.user,
+        operator_url,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, time::Duration};
+
+    use super::with_operator_auth;
+    use crate::{
+        AdeptError, AdeptStep, AdobeAccount, Clock, Error, FulfillParams,
+        error::StepContext,
+        http_client::testing::MockHttpClient,
+        make_signer,
+        testing::{ADEPT_URL, OPERATOR_URL, TestClock, respond_operator_auth, test_account},
+    };
+
+    #[tokio::test]
+    async fn test_operator_auth_cache() {
+        let http = MockHttpClient::new();
+        let mut account = test_account();
+        let signer = make_signer(&account.user_credentials.private_auth_key).unwrap();
+        let clock = TestClock::new();
+        let params = FulfillParams {
+            clock: clock.clone(),
+            operator_auth_ttl: Duration::from_secs(60 * 60),
+            ..Default::default()
+        };
+        let auth_requests =
+            |http: &MockHttpClient| http.urls().iter().filter(|x| x.ends_with("/Auth")).count();
+        let request = async |_: &AdobeAccount| Ok::<_, Error>(());
+
+        // The first request authenticates, and records it in the account.
+        respond_operator_auth(&http);
+        with_operator_auth(
+            &http,
+            &signer,
+            OPERATOR_URL,
+            &mut account,
+            &params,
+            AdeptStep::Fulfill,
+            request,
+        )
+        .await
+        .expect("with_operator_auth failed");
+        assert_eq!(auth_requests(&http), 1);
+        assert_eq!(account.authorized_operators.len(), 1);
+        assert_eq!(account.authorized_operators[0].authorized_at, clock.now());
+
+        // Until the TTL expires, the authentication is reused.
+        clock.advance(Duration::from_secs(30 * 60));
+        with_operator_auth(
+            &http,
+            &signer,
+            OPERATOR_URL,
+            &mut account,
+            &params,
+            AdeptStep::Fulfill,
+            request,
+        )
+        .await
+        .expect("with_operator_auth failed");
+        assert_eq!(auth_requests(&http), 1);
+
+        clock.advance(Duration::from_secs(30 * 60));
+        respond_operator_auth(&http);
+        with_operator_auth(
+            &http,
+            &signer,
+            OPERATOR_URL,
+            &mut account,
+            &params,
+            AdeptStep::Fulfill,
+            request,
+        )
+        .await
+        .expect("with_operator_auth failed");
+        assert_eq!(auth_requests(&http), 2);
+        assert_eq!(account.authorized_operators.len(), 1);
+        assert_eq!(account.authorized_operators[0].authorized_at, clock.now());
+    }
+
+    #[tokio::test]
+    async fn test_operator_auth_retry() {
+        let http = MockHttpClient::new();
+        let mut account = test_account();
+        let signer = make_signer(&account.user_credentials.private_auth_key).unwrap();
+        let params = FulfillParams {
+            clock: TestClock::new(),
+            ..Default::default()
+        };
+        account.set_operator_authorized(OPERATOR_URL, Some(params.clock.now()));
+
+        let calls = Cell::new(0);
+        let failing_once = |code: &'static str| {
+            let calls = &calls;
+            async move |_: &AdobeAccount| {
+                calls.set(calls.get() + 1);
+                if calls.get() == 1 {
+                    Err(Error::from(AdeptError::new(code.to_string(), Vec::new())))
+                        .step(AdeptStep::Fulfill)
+                } else {
+                    Ok(())
+                }
+            }
+        };
+
+        // The operator dropped the cached authentication, so it is done again.
+        respond_operator_auth(&http);
+        with_operator_auth(
+            &http,
+            &signer,
+            OPERATOR_URL,
+            &mut account,
+            &params,
+            AdeptStep::Fulfill,
+            failing_once("E_AUTH_FAILED"),
+        )
+        .await
+        .expect("with_operator_auth failed");
+        assert_eq!(calls.get(), 2);
+        assert_eq!(
+            http.urls(),
+            [
+                format!("{OPERATOR_URL}/Auth"),
+                format!("{ADEPT_URL}/InitLicenseService")
+            ]
+        );
+
+        // Other errors are not caused by the cache, so they are returned as is.
+        calls.set(0);
+        let err = with_operator_auth(
+            &http,
+            &signer,
+            OPERATOR_URL,
+            &mut account,
+            &params,
+            AdeptStep::Fulfill,
+            failing_once("E_LIC_ALREADY_FULFILLED_BY_ANOTHER_USER"),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(calls.get(), 1);
+        assert_eq!(http.urls().len(), 2);
+        assert_eq!(
+            err.adept_error().map(|x| &x.code),
+            Some(&crate::AdeptErrorCode::AlreadyFulfilledByAnotherUser)
+        );
+        assert!(account.is_operator_authorized(
+            OPERATOR_URL,
+            params.clock.now(),
+            params.operator_auth_ttl
+        ));
+    }
+}
@@ -2,7 +2,7 @@ pub mod serde_base64 {
     use adobededrmtools_crypto::{b64, unb64};
     use serde::{Deserialize, Deserializer, Serializer, de::Error};
 
-    pub fn serialize<S>(bytes: &Vec<u8>, s: S) -> Result<S::Ok, S::Error>
+    pub fn serialize<S>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
@@ -26,3 +26,25 @@ pub mod serde_base64 {
         }
     }
 }
+
+/// Like [`serde_base64`], for keys of a fixed length.
+pub mod serde_base64_array {
+    use serde::{Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S, const N: usize>(bytes: &[u8; N], s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        super::serde_base64::serialize(bytes, s)
+    }
+
+    pub fn deserialize<'de, D, const N: usize>(d: D) -> Result<[u8; N], D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bytes = super::serde_base64::deserialize(d)?;
+        bytes.try_into().map_err(|x: Vec<u8>| {
+            D::Error::custom(format!("expected {} bytes, got {}", N, x.len()))
+        })
+    }
+}
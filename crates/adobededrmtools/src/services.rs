@@ -1,29 +1,57 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
 use super::{HttpClient, adept};
 use adobededrmtools_crypto::unb64;
-use anyhow::Context;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::serializarion::serde_base64;
+use crate::{
+    AdeptStep, Clock, Error, SystemClock,
+    error::{Context, StepContext},
+};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AdobeServicesInfo {
     pub activation_url: String,
+    pub user_info_url: String,
+    #[serde(with = "serde_base64")]
+    pub activation_certificate: Vec<u8>,
     pub auth_service: AdobeAuthServiceInfo,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AdobeAuthServiceInfo {
     pub auth_url: String,
+    #[serde(with = "serde_base64")]
     pub auth_certificate: Vec<u8>,
-    pub sign_in_methods: Vec<String>,
+    pub sign_in_methods: Vec<SignInMethodInfo>,
+}
+
+/// Sign in method advertised by the authentication service.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignInMethodInfo {
+    /// Method identifier, e.g. `anonymous` or `AdobeID`.
+    pub method: String,
+    /// Method type, e.g. `anonymous` or `standard`.
+    pub method_type: String,
+    /// Human-readable name of the method.
+    pub name: String,
 }
 
 pub async fn get_services_info<H: HttpClient>(
     http_client: &H,
     activation_url: &str,
-) -> anyhow::Result<AdobeServicesInfo> {
+) -> crate::Result<AdobeServicesInfo> {
     // Activation service info
     let asi = adept::get_activation_service_info(http_client, activation_url)
         .await
-        .context("get_activation_service_info failed")?;
+        .step(AdeptStep::ActivationServiceInfo)?;
 
     log::debug!("activation service info: {:?}", asi);
 
@@ -32,11 +60,14 @@ pub async fn get_services_info<H: HttpClient>(
     // Authentication service info
     let auth = adept::get_authentication_service_info(http_client, &auth_url)
         .await
-        .context("get_authentication_service_info failed")?;
+        .step(AdeptStep::AuthenticationServiceInfo)?;
 
     log::debug!("auth: {:?}", auth);
     Ok(AdobeServicesInfo {
         activation_url: activation_url.to_string(),
+        user_info_url: asi.user_info_url,
+        activation_certificate: unb64(&asi.certificate)
+            .context("invalid activation certificate")?,
         auth_service: AdobeAuthServiceInfo {
             auth_url,
             auth_certificate: unb64(&auth.certificate)?,
@@ -44,8 +75,395 @@ pub async fn get_services_info<H: HttpClient>(
                 .sign_in_methods
                 .sign_in_methods
                 .into_iter()
-                .map(|v| v.method)
+                .map(|v| SignInMethodInfo {
+                    method: v.method,
+                    method_type: v.method_type,
+                    name: v.name,
+                })
                 .collect(),
         },
     })
 }
+
+const DEFAULT_SERVICES_INFO_TTL: Duration = Duration::from_secs(60 * 60 * 24 * 7);
+
+/// Services info together with the time it was fetched at.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedServicesInfo {
+    pub services: AdobeServicesInfo,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// Certificate of a license service together with the time it was fetched at.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedLicenseCertificate {
+    #[serde(with = "serde_base64")]
+    pub certificate: Vec<u8>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// Storage for the services info, keyed by the activation URL, and for the certificates of the
+/// license services the license tokens are checked against, keyed by the license URL.
+pub trait ServicesInfoCache: Send + Sync {
+    fn get(&self, activation_url: &str) -> crate::Result<Option<CachedServicesInfo>>;
+
+    fn put(&self, activation_url: &str, info: CachedServicesInfo) -> crate::Result<()>;
+
+    fn get_license_certificate(
+        &self,
+        license_url: &str,
+    ) -> crate::Result<Option<CachedLicenseCertificate>>;
+
+    fn put_license_certificate(
+        &self,
+        license_url: &str,
+        certificate: CachedLicenseCertificate,
+    ) -> crate::Result<()>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryServicesInfoCache {
+    entries: Mutex<HashMap<String, CachedServicesInfo>>,
+    license_certificates: Mutex<HashMap<String, CachedLicenseCertificate>>,
+}
+
+impl ServicesInfoCache for MemoryServicesInfoCache {
+    fn get(&self, activation_url: &str) -> crate::Result<Option<CachedServicesInfo>> {
+        let entries = self.entries.lock().expect("services info cache poisoned");
+        Ok(entries.get(activation_url).cloned())
+    }
+
+    fn put(&self, activation_url: &str, info: CachedServicesInfo) -> crate::Result<()> {
+        let mut entries = self.entries.lock().expect("services info cache poisoned");
+        entries.insert(activation_url.to_string(), info);
+        Ok(())
+    }
+
+    fn get_license_certificate(
+        &self,
+        license_url: &str,
+    ) -> crate::Result<Option<CachedLicenseCertificate>> {
+        let certificates = self
+            .license_certificates
+            .lock()
+            .expect("services info cache poisoned");
+        Ok(certificates.get(license_url).cloned())
+    }
+
+    fn put_license_certificate(
+        &self,
+        license_url: &str,
+        certificate: CachedLicenseCertificate,
+    ) -> crate::Result<()> {
+        let mut certificates = self
+            .license_certificates
+            .lock()
+            .expect("services info cache poisoned");
+        certificates.insert(license_url.to_string(), certificate);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ServicesInfoCacheFile {
+    #[serde(default)]
+    services: HashMap<String, CachedServicesInfo>,
+    #[serde(default)]
+    license_certificates: HashMap<String, CachedLicenseCertificate>,
+}
+
+/// Keeps the services info in a JSON file, so it survives between runs.
+#[derive(Debug)]
+pub struct FileServicesInfoCache {
+    path: PathBuf,
+}
+
+impl FileServicesInfoCache {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn read(&self) -> crate::Result<ServicesInfoCacheFile> {
+        match std::fs::read(&self.path) {
+            Ok(data) => serde_json::from_slice(&data).context("invalid services info cache"),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Default::default()),
+            Err(err) => Err(err).context("could not read services info cache"),
+        }
+    }
+
+    fn write(&self, file: &ServicesInfoCacheFile) -> crate::Result<()> {
+        std::fs::write(&self.path, serde_json::to_vec_pretty(file)?)
+            .context("could not write services info cache")
+    }
+}
+
+impl ServicesInfoCache for FileServicesInfoCache {
+    fn get(&self, activation_url: &str) -> crate::Result<Option<CachedServicesInfo>> {
+        Ok(self.read()?.services.remove(activation_url))
+    }
+
+    fn put(&self, activation_url: &str, info: CachedServicesInfo) -> crate::Result<()> {
+        let mut file = self.read()?;
+        file.services.insert(activation_url.to_string(), info);
+        self.write(&file)
+    }
+
+    fn get_license_certificate(
+        &self,
+        license_url: &str,
+    ) -> crate::Result<Option<CachedLicenseCertificate>> {
+        Ok(self.read()?.license_certificates.remove(license_url))
+    }
+
+    fn put_license_certificate(
+        &self,
+        license_url: &str,
+        certificate: CachedLicenseCertificate,
+    ) -> crate::Result<()> {
+        let mut file = self.read()?;
+        file.license_certificates
+            .insert(license_url.to_string(), certificate);
+        self.write(&file)
+    }
+}
+
+/// How the services info and the certificates of license services are looked up.
+#[derive(Clone)]
+pub struct ServicesInfoParams {
+    /// Cache to look the services info and certificates up in first. The services are always
+    /// queried without one.
+    pub cache: Option<Arc<dyn ServicesInfoCache>>,
+    /// How long a cached entry is used for.
+    pub ttl: Duration,
+    /// Never query the services. Cached entries are used regardless of their age.
+    pub offline: bool,
+    pub clock: Arc<dyn Clock>,
+}
+
+impl Default for ServicesInfoParams {
+    fn default() -> Self {
+        Self {
+            cache: None,
+            ttl: DEFAULT_SERVICES_INFO_TTL,
+            offline: false,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl ServicesInfoParams {
+    /// Whether an entry cached at `fetched_at` is used instead of querying the service.
+    fn is_usable(&self, fetched_at: DateTime<Utc>) -> bool {
+        let fresh = (self.clock.now() - fetched_at)
+            .to_std()
+            .is_ok_and(|age| age < self.ttl);
+        fresh || self.offline
+    }
+}
+
+/// Like [`get_services_info`], but goes through the cache of `params`.
+pub async fn get_cached_services_info<H: HttpClient>(
+    http_client: &H,
+    activation_url: &str,
+    params: &ServicesInfoParams,
+) -> crate::Result<AdobeServicesInfo> {
+    let now = params.clock.now();
+
+    if let Some(cache) = &params.cache
+        && let Some(cached) = cache.get(activation_url)?
+    {
+        if params.is_usable(cached.fetched_at) {
+            return Ok(cached.services);
+        }
+        log::debug!("cached services info for {} is stale", activation_url);
+    }
+
+    if params.offline {
+        return Err(Error::Offline(format!(
+            "services info for {} is not cached",
+            activation_url
+        )));
+    }
+
+    let services = get_services_info(http_client, activation_url).await?;
+
+    if let Some(cache) = &params.cache {
+        cache.put(
+            activation_url,
+            CachedServicesInfo {
+                services: services.clone(),
+                fetched_at: now,
+            },
+        )?;
+    }
+
+    Ok(services)
+}
+
+/// Gets the certificate of the license service at `license_url` that signs the license tokens,
+/// going through the cache of `params`.
+pub async fn get_cached_license_certificate<H: HttpClient>(
+    http_client: &H,
+    license_url: &str,
+    params: &ServicesInfoParams,
+) -> crate::Result<Vec<u8>> {
+    if let Some(cache) = &params.cache
+        && let Some(cached) = cache.get_license_certificate(license_url)?
+    {
+        if params.is_usable(cached.fetched_at) {
+            return Ok(cached.certificate);
+        }
+        log::debug!("cached certificate of {} is stale", license_url);
+    }
+
+    if params.offline {
+        return Err(Error::Offline(format!(
+            "certificate of license service {} is not cached",
+            license_url
+        )));
+    }
+
+    let info = adept::get_license_service_info(http_client, license_url)
+        .await
+        .step(AdeptStep::LicenseServiceInfo)?;
+    let certificate = unb64(&info.certificate).context("invalid license service certificate")?;
+
+    if let Some(cache) = &params.cache {
+        cache.put_license_certificate(
+            license_url,
+            CachedLicenseCertificate {
+                certificate: certificate.clone(),
+                fetched_at: params.clock.now(),
+            },
+        )?;
+    }
+
+    Ok(certificate)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use adobededrmtools_crypto::b64;
+
+    use super::{
+        AdobeAuthServiceInfo, AdobeServicesInfo, CachedLicenseCertificate, CachedServicesInfo,
+        FileServicesInfoCache, MemoryServicesInfoCache, ServicesInfoCache, ServicesInfoParams,
+        get_cached_license_certificate,
+    };
+    use crate::{
+        Error,
+        http_client::testing::MockHttpClient,
+        testing::{TestClock, USER_CERTIFICATE},
+    };
+
+    const LICENSE_URL: &str = "https://nasigningservice.adobe.com/licensesign";
+
+    #[test]
+    fn test_file_services_info_cache() {
+        let path = std::env::temp_dir().join(format!(
+            "adobededrmtools-services-cache-{}.json",
+            std::process::id()
+        ));
+        let cache = FileServicesInfoCache::new(&path);
+        let activation_url = "https://adeactivate.adobe.com/adept";
+
+        assert!(cache.get(activation_url).unwrap().is_none());
+
+        let info = CachedServicesInfo {
+            services: AdobeServicesInfo {
+                activation_url: activation_url.to_string(),
+                user_info_url: format!("{}/UserInfo", activation_url),
+                activation_certificate: vec![1, 2, 3],
+                auth_service: AdobeAuthServiceInfo {
+                    auth_url: "https://adeactivate.adobe.com/adept".to_string(),
+                    auth_certificate: vec![4, 5, 6],
+                    sign_in_methods: Vec::new(),
+                },
+            },
+            fetched_at: "2025-03-01T18:00:00Z".parse().unwrap(),
+        };
+        cache.put(activation_url, info.clone()).unwrap();
+        cache
+            .put_license_certificate(
+                LICENSE_URL,
+                CachedLicenseCertificate {
+                    certificate: vec![7, 8, 9],
+                    fetched_at: info.fetched_at,
+                },
+            )
+            .unwrap();
+
+        let reopened = FileServicesInfoCache::new(&path);
+        let cached = reopened
+            .get(activation_url)
+            .unwrap()
+            .expect("services info is not cached");
+        let certificate = reopened
+            .get_license_certificate(LICENSE_URL)
+            .unwrap()
+            .expect("license certificate is not cached");
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(certificate.certificate, vec![7, 8, 9]);
+
+        assert_eq!(cached.fetched_at, info.fetched_at);
+        assert_eq!(cached.services.user_info_url, info.services.user_info_url);
+        assert_eq!(cached.services.activation_certificate, vec![1, 2, 3]);
+        assert_eq!(cached.services.auth_service.auth_certificate, vec![4, 5, 6]);
+    }
+
+    #[tokio::test]
+    async fn test_cached_license_certificate() {
+        let http = MockHttpClient::new();
+        let clock = TestClock::new();
+        let params = ServicesInfoParams {
+            cache: Some(Arc::new(MemoryServicesInfoCache::default())),
+            ttl: Duration::from_secs(60 * 60),
+            clock: clock.clone(),
+            ..Default::default()
+        };
+        let respond_license_service_info = || {
+            http.respond(
+                &format!("{LICENSE_URL}/LicenseServiceInfo"),
+                &format!(
+                    r#"<licenseServiceInfo xmlns="http://ns.adobe.com/adept"><licenseURL>{LICENSE_URL}</licenseURL><certificate>{}</certificate></licenseServiceInfo>"#,
+                    b64(USER_CERTIFICATE)
+                ),
+            )
+        };
+
+        // The certificate is only fetched again once the cached one is stale.
+        respond_license_service_info();
+        for _ in 0..2 {
+            let certificate = get_cached_license_certificate(&http, LICENSE_URL, &params)
+                .await
+                .expect("get_cached_license_certificate failed");
+            assert_eq!(certificate, USER_CERTIFICATE);
+        }
+        assert_eq!(http.requests().len(), 1);
+
+        clock.advance(Duration::from_secs(2 * 60 * 60));
+        respond_license_service_info();
+        get_cached_license_certificate(&http, LICENSE_URL, &params)
+            .await
+            .expect("get_cached_license_certificate failed");
+        assert_eq!(http.requests().len(), 2);
+
+        // Offline, only cached certificates are used, whatever their age.
+        let params = ServicesInfoParams {
+            offline: true,
+            ..params
+        };
+        clock.advance(Duration::from_secs(2 * 60 * 60));
+        get_cached_license_certificate(&http, LICENSE_URL, &params)
+            .await
+            .expect("get_cached_license_certificate failed");
+        let err = get_cached_license_certificate(&http, "https://other.example.com", &params)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Offline(_)));
+        assert_eq!(http.requests().len(), 2);
+    }
+}
@@ -0,0 +1,178 @@
+//! Fixtures shared by the tests of the crate.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use adobededrmtools_crypto::{Pkey, b64, decrypt_aes, make_pkcs12, unb64};
+use chrono::{DateTime, Utc};
+
+use crate::{
+    AccountDevice, ActivatedDevice, AdobeAccount, AdobeAuthServiceInfo, AdobeMinServicesInfo,
+    AdobeServicesInfo, CachedServicesInfo, Clock, DeviceInfo, MemoryServicesInfoCache,
+    ServicesInfoCache, ServicesInfoParams, SignInMethodInfo, UserCredentials,
+    http_client::testing::MockHttpClient,
+};
+
+pub const USER_KEY: &[u8] = include_bytes!("../testdata/user_key.der");
+pub const USER_CERTIFICATE: &[u8] = include_bytes!("../testdata/user_certificate.der");
+pub const ADEPT_URL: &str = "https://adeactivate.adobe.com/adept";
+pub const USER: &str = "urn:uuid:4b4e7bd5-4b2b-4bb5-a2a4-3a0e5f0cbd2b";
+pub const OPERATOR_URL: &str = "https://acs.example.com/fulfillment";
+/// Id of the device of [`test_account`].
+pub const DEVICE: &str = "urn:uuid:00000000-0000-0000-0000-000000000001";
+pub const FINGERPRINT: &str = "ZmluZ2VycHJpbnQ=";
+pub const AUTH_URL: &str = "https://auth.example.com/adept";
+
+pub fn test_device(device: &str, fingerprint: &str) -> AccountDevice {
+    AccountDevice {
+        device_info: DeviceInfo {
+            fingerprint: fingerprint.to_string(),
+            ..DeviceInfo::generate()
+        },
+        activation_token: ActivatedDevice {
+            device: device.to_string(),
+            fingerprint: fingerprint.to_string(),
+            device_type: "standalone".to_string(),
+            activation_url: ADEPT_URL.to_string(),
+            user: USER.to_string(),
+            signature: "c2lnbmF0dXJl".to_string(),
+        },
+        identity: None,
+    }
+}
+
+/// Anonymous account with one device, signing with the test user key.
+pub fn test_account() -> AdobeAccount {
+    crate::init_test_rand();
+
+    AdobeAccount {
+        services: AdobeMinServicesInfo {
+            activation_url: ADEPT_URL.to_string(),
+            auth_url: ADEPT_URL.to_string(),
+            auth_certificate: USER_CERTIFICATE.to_vec(),
+            user_info_url: Some(ADEPT_URL.to_string()),
+        },
+        user_credentials: UserCredentials {
+            user: USER.to_string(),
+            username: None,
+            private_auth_key: USER_KEY.to_vec(),
+            user_certificate: USER_CERTIFICATE.to_vec(),
+            private_license_key: USER_KEY.to_vec(),
+            license_certificate: USER_CERTIFICATE.to_vec(),
+        },
+        devices: vec![test_device(DEVICE, FINGERPRINT)],
+        authorized_operators: Vec::new(),
+    }
+}
+
+/// Queues the responses to authenticating with the test operator.
+pub fn respond_operator_auth(http: &MockHttpClient) {
+    http.respond(&format!("{OPERATOR_URL}/Auth"), "<success/>");
+    http.respond(&format!("{ADEPT_URL}/InitLicenseService"), "<success/>");
+}
+
+/// Services info of [`ADEPT_URL`], only looked up in a cache. The authentication service
+/// offers the anonymous and Adobe ID sign in methods, and encrypts for [`USER_CERTIFICATE`].
+pub fn cached_services_info() -> ServicesInfoParams {
+    let sign_in_method = |method: &str, method_type: &str| SignInMethodInfo {
+        method: method.to_string(),
+        method_type: method_type.to_string(),
+        name: method.to_string(),
+    };
+    let cache = MemoryServicesInfoCache::default();
+    cache
+        .put(
+            ADEPT_URL,
+            CachedServicesInfo {
+                services: AdobeServicesInfo {
+                    activation_url: ADEPT_URL.to_string(),
+                    user_info_url: ADEPT_URL.to_string(),
+                    activation_certificate: USER_CERTIFICATE.to_vec(),
+                    auth_service: AdobeAuthServiceInfo {
+                        auth_url: AUTH_URL.to_string(),
+                        auth_certificate: USER_CERTIFICATE.to_vec(),
+                        sign_in_methods: vec![
+                            sign_in_method("anonymous", "anonymous"),
+                            sign_in_method("AdobeID", "standard"),
+                        ],
+                    },
+                },
+                fetched_at: "2020-01-01T00:00:00Z".parse().unwrap(),
+            },
+        )
+        .unwrap();
+
+    ServicesInfoParams {
+        cache: Some(Arc::new(cache)),
+        offline: true,
+        ..Default::default()
+    }
+}
+
+fn element_text<'a>(xml: &'a str, name: &str) -> &'a str {
+    let start = xml.find(&format!("<{name}>")).expect("no such element") + name.len() + 2;
+    let end = xml
+        .find(&format!("</{name}>"))
+        .expect("element is not closed");
+    &xml[start..end]
+}
+
+/// Queues a successful sign in to [`AUTH_URL`] as `user`, made the way the authentication
+/// service does from the keys in the request.
+pub fn respond_sign_in(http: &MockHttpClient, user: &str) {
+    let user = user.to_string();
+    http.respond_with(&format!("{AUTH_URL}/SignInDirect"), move |body| {
+        let credentials = Pkey::from_der(USER_KEY)
+            .unwrap()
+            .decrypt(&unb64(element_text(body, "adept:signInData")).unwrap())
+            .unwrap();
+        let key: [u8; 16] = credentials[..16].try_into().unwrap();
+        let private_auth_key = decrypt_aes(
+            &key,
+            &unb64(element_text(body, "adept:encryptedPrivateAuthKey")).unwrap(),
+        )
+        .unwrap();
+        let pkcs12 = make_pkcs12(&private_auth_key, USER_CERTIFICATE, &b64(&key)).unwrap();
+
+        format!(
+            r#"<credentials xmlns="http://ns.adobe.com/adept"><user>{user}</user><pkcs12>{}</pkcs12><encryptedPrivateLicenseKey>{}</encryptedPrivateLicenseKey><licenseCertificate>{}</licenseCertificate></credentials>"#,
+            b64(&pkcs12),
+            element_text(body, "adept:encryptedPrivateLicenseKey"),
+            b64(USER_CERTIFICATE)
+        )
+    });
+}
+
+/// Activation token the activation service issues for activating `user` as `device`.
+pub fn activation_token(user: &str, device: &str, fingerprint: &str) -> String {
+    format!(
+        "<activationToken><device>{device}</device><fingerprint>{fingerprint}</fingerprint>\
+         <deviceType>standalone</deviceType><activationURL>{ADEPT_URL}</activationURL>\
+         <user>{user}</user><signature>c2lnbmF0dXJl</signature></activationToken>"
+    )
+}
+
+/// Clock that only moves when told to.
+pub struct TestClock(Mutex<DateTime<Utc>>);
+
+impl TestClock {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self(Mutex::new(
+            DateTime::parse_from_rfc3339("2025-03-01T10:00:00Z")
+                .unwrap()
+                .into(),
+        )))
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.0.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap()
+    }
+}
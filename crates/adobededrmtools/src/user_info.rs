@@ -0,0 +1,49 @@
+use super::{HttpClient, adept, make_expiration, random_nonce};
+use adobededrmtools_crypto::Signer;
+
+use crate::{AdeptStep, error::StepContext};
+
+#[derive(Debug, Clone)]
+pub struct UserInfo {
+    pub user: String,
+    /// Sign in method of the user, `anonymous` if the user has no username.
+    pub sign_in_method: String,
+    pub username: Option<String>,
+    pub activation_count: Option<u32>,
+    pub max_activation_count: Option<u32>,
+}
+
+pub async fn fetch_user_info<H: HttpClient>(
+    http_client: &H,
+    signer: &Signer,
+    user_info_url: &str,
+    user: &str,
+) -> crate::Result<UserInfo> {
+    let user_info = adept::get_user_info(
+        http_client,
+        signer,
+        user_info_url,
+        adept::UserInfoData {
+            user: user.to_string(),
+            nonce: random_nonce(),
+            expiration: make_expiration(),
+        },
+    )
+    .await
+    .step(AdeptStep::UserInfo)?;
+
+    log::debug!("user info: {:?}", user_info);
+
+    let (sign_in_method, username) = match user_info.username {
+        Some(username) => (username.method, Some(username.username)),
+        None => ("anonymous".to_string(), None),
+    };
+
+    Ok(UserInfo {
+        user: user_info.user,
+        sign_in_method,
+        username,
+        activation_count: user_info.activation_count,
+        max_activation_count: user_info.max_activation_count,
+    })
+}